@@ -0,0 +1,11 @@
+DROP TRIGGER project_increment_version ON project;
+
+DROP TRIGGER todo_increment_version ON todo;
+
+DROP FUNCTION increment_version;
+
+ALTER TABLE
+    project DROP COLUMN version;
+
+ALTER TABLE
+    todo DROP COLUMN version;
\ No newline at end of file
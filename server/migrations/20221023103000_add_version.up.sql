@@ -0,0 +1,24 @@
+ALTER TABLE
+    todo
+ADD
+    COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+ALTER TABLE
+    project
+ADD
+    COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE FUNCTION increment_version() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER todo_increment_version BEFORE
+UPDATE
+    ON todo FOR EACH ROW EXECUTE FUNCTION increment_version();
+
+CREATE TRIGGER project_increment_version BEFORE
+UPDATE
+    ON project FOR EACH ROW EXECUTE FUNCTION increment_version();
@@ -0,0 +1,27 @@
+DROP INDEX todo_by_account_id_updated_at;
+
+DROP TRIGGER todo_file_set_updated_at ON todo_file;
+
+DROP TRIGGER tag_set_updated_at ON tag;
+
+DROP TRIGGER project_set_updated_at ON project;
+
+DROP TRIGGER todo_set_updated_at ON todo;
+
+DROP FUNCTION set_updated_at;
+
+ALTER TABLE
+    todo_file DROP COLUMN updated_at,
+    DROP COLUMN created_at;
+
+ALTER TABLE
+    tag DROP COLUMN updated_at,
+    DROP COLUMN created_at;
+
+ALTER TABLE
+    project DROP COLUMN updated_at,
+    DROP COLUMN created_at;
+
+ALTER TABLE
+    todo DROP COLUMN updated_at,
+    DROP COLUMN created_at;
\ No newline at end of file
@@ -0,0 +1,52 @@
+ALTER TABLE
+    todo
+ADD
+    COLUMN created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+ADD
+    COLUMN updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC');
+
+ALTER TABLE
+    project
+ADD
+    COLUMN created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+ADD
+    COLUMN updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC');
+
+ALTER TABLE
+    tag
+ADD
+    COLUMN created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+ADD
+    COLUMN updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC');
+
+ALTER TABLE
+    todo_file
+ADD
+    COLUMN created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+ADD
+    COLUMN updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC');
+
+CREATE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at := CURRENT_TIMESTAMP AT TIME ZONE 'UTC';
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER todo_set_updated_at BEFORE
+UPDATE
+    ON todo FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER project_set_updated_at BEFORE
+UPDATE
+    ON project FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER tag_set_updated_at BEFORE
+UPDATE
+    ON tag FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER todo_file_set_updated_at BEFORE
+UPDATE
+    ON todo_file FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+CREATE INDEX todo_by_account_id_updated_at ON todo (account_id, updated_at);
\ No newline at end of file
@@ -0,0 +1,3 @@
+DROP FUNCTION allocate_project_todo_number;
+
+DROP TABLE project_todo_counter;
\ No newline at end of file
@@ -0,0 +1,28 @@
+CREATE TABLE project_todo_counter (
+    project_id BIGINT PRIMARY KEY REFERENCES project(id) ON DELETE CASCADE,
+    last_todo_number INT NOT NULL
+);
+
+INSERT INTO
+    project_todo_counter (project_id, last_todo_number)
+SELECT
+    project_id,
+    MAX(project_todo_number)
+FROM
+    todo
+WHERE
+    project_id IS NOT NULL
+GROUP BY
+    project_id;
+
+-- Allocates the next todo number of a project. Numbers are never reused, even
+-- after the todo holding them is deleted or moved to another project.
+CREATE FUNCTION allocate_project_todo_number(target_project_id BIGINT) RETURNS INT AS $$
+    INSERT INTO
+        project_todo_counter (project_id, last_todo_number)
+    VALUES
+        (target_project_id, 1)
+    ON CONFLICT (project_id)
+    DO UPDATE SET last_todo_number = project_todo_counter.last_todo_number + 1
+    RETURNING last_todo_number;
+$$ LANGUAGE SQL;
\ No newline at end of file
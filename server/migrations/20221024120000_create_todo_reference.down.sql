@@ -0,0 +1,7 @@
+DROP TRIGGER todo_record_reference ON todo;
+
+DROP FUNCTION record_todo_reference;
+
+DROP INDEX todo_reference_by_todo_id;
+
+DROP TABLE todo_reference;
\ No newline at end of file
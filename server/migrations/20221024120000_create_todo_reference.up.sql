@@ -0,0 +1,43 @@
+-- Every project reference (such as `ABC-12`) a todo has ever held, so that
+-- references keep resolving after a todo moves between projects.
+CREATE TABLE todo_reference (
+    project_id BIGINT NOT NULL REFERENCES project(id) ON DELETE CASCADE,
+    project_todo_number INT NOT NULL,
+    todo_id BIGINT NOT NULL REFERENCES todo(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    PRIMARY KEY (project_id, project_todo_number)
+);
+
+CREATE INDEX todo_reference_by_todo_id ON todo_reference(todo_id);
+
+INSERT INTO
+    todo_reference (project_id, project_todo_number, todo_id)
+SELECT
+    project_id,
+    project_todo_number,
+    id
+FROM
+    todo
+WHERE
+    project_id IS NOT NULL;
+
+CREATE FUNCTION record_todo_reference() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.project_id IS NOT NULL THEN
+        INSERT INTO
+            todo_reference (project_id, project_todo_number, todo_id)
+        VALUES
+            (NEW.project_id, NEW.project_todo_number, NEW.id)
+        ON CONFLICT DO NOTHING;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER todo_record_reference
+AFTER
+INSERT
+    OR
+UPDATE
+    OF project_id,
+    project_todo_number ON todo FOR EACH ROW EXECUTE FUNCTION record_todo_reference();
\ No newline at end of file
@@ -0,0 +1,15 @@
+ALTER TABLE
+    todo_tag DROP CONSTRAINT todo_tag_pkey;
+
+ALTER TABLE
+    todo_tag
+ALTER COLUMN
+    todo_id DROP NOT NULL,
+ALTER COLUMN
+    tag_id DROP NOT NULL;
+
+ALTER TABLE
+    tag DROP CONSTRAINT tag_account_id_tag_name_unique;
+
+ALTER TABLE
+    tag DROP COLUMN color;
\ No newline at end of file
@@ -0,0 +1,27 @@
+ALTER TABLE
+    tag
+ADD
+    COLUMN color TEXT;
+
+ALTER TABLE
+    tag
+ADD
+    CONSTRAINT tag_account_id_tag_name_unique UNIQUE(account_id, tag_name);
+
+DELETE FROM
+    todo_tag
+WHERE
+    todo_id IS NULL
+    OR tag_id IS NULL;
+
+DELETE FROM
+    todo_tag duplicate USING todo_tag original
+WHERE
+    duplicate.ctid > original.ctid
+    AND duplicate.todo_id = original.todo_id
+    AND duplicate.tag_id = original.tag_id;
+
+ALTER TABLE
+    todo_tag
+ADD
+    PRIMARY KEY (todo_id, tag_id);
\ No newline at end of file
@@ -0,0 +1,16 @@
+DROP FUNCTION tag_subtree;
+
+DROP FUNCTION tag_path;
+
+ALTER TABLE
+    tag DROP CONSTRAINT tag_account_id_parent_tag_id_tag_name_unique;
+
+DROP INDEX tag_by_parent_tag_id;
+
+ALTER TABLE
+    tag DROP COLUMN parent_tag_id;
+
+ALTER TABLE
+    tag
+ADD
+    CONSTRAINT tag_account_id_tag_name_unique UNIQUE(account_id, tag_name);
\ No newline at end of file
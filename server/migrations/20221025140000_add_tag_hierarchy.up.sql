@@ -0,0 +1,60 @@
+ALTER TABLE
+    tag
+ADD
+    COLUMN parent_tag_id BIGINT REFERENCES tag(id) ON DELETE CASCADE;
+
+CREATE INDEX tag_by_parent_tag_id ON tag(parent_tag_id);
+
+ALTER TABLE
+    tag DROP CONSTRAINT tag_account_id_tag_name_unique;
+
+ALTER TABLE
+    tag
+ADD
+    CONSTRAINT tag_account_id_parent_tag_id_tag_name_unique UNIQUE NULLS NOT DISTINCT (account_id, parent_tag_id, tag_name);
+
+-- The full path of a tag, such as `work/clients/acme`.
+CREATE FUNCTION tag_path(target_tag_id BIGINT) RETURNS TEXT AS $$
+    WITH RECURSIVE ancestor(id, parent_tag_id, tag_name, depth) AS (
+        SELECT
+            id,
+            parent_tag_id,
+            tag_name,
+            0
+        FROM
+            tag
+        WHERE
+            id = target_tag_id
+        UNION ALL
+        SELECT
+            tag.id,
+            tag.parent_tag_id,
+            tag.tag_name,
+            ancestor.depth + 1
+        FROM
+            tag
+            JOIN ancestor ON tag.id = ancestor.parent_tag_id
+    )
+    SELECT
+        string_agg(tag_name, '/' ORDER BY depth DESC)
+    FROM
+        ancestor;
+$$ LANGUAGE SQL STABLE;
+
+-- The IDs of a tag and all of its descendants.
+CREATE FUNCTION tag_subtree(root_tag_id BIGINT) RETURNS SETOF BIGINT AS $$
+    WITH RECURSIVE descendant(id) AS (
+        SELECT
+            root_tag_id
+        UNION
+        SELECT
+            tag.id
+        FROM
+            tag
+            JOIN descendant ON tag.parent_tag_id = descendant.id
+    )
+    SELECT
+        id
+    FROM
+        descendant;
+$$ LANGUAGE SQL STABLE;
\ No newline at end of file
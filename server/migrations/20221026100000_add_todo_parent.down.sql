@@ -0,0 +1,6 @@
+DROP FUNCTION todo_subtree;
+
+DROP INDEX todo_by_parent_todo_id;
+
+ALTER TABLE
+    todo DROP COLUMN parent_todo_id;
\ No newline at end of file
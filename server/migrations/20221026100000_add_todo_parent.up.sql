@@ -0,0 +1,24 @@
+ALTER TABLE
+    todo
+ADD
+    COLUMN parent_todo_id BIGINT REFERENCES todo(id) ON DELETE CASCADE;
+
+CREATE INDEX todo_by_parent_todo_id ON todo (parent_todo_id);
+
+-- The IDs of a todo and all of its subtasks, at any depth.
+CREATE FUNCTION todo_subtree(root_todo_id BIGINT) RETURNS SETOF BIGINT AS $$
+    WITH RECURSIVE descendant(id) AS (
+        SELECT
+            root_todo_id
+        UNION
+        SELECT
+            todo.id
+        FROM
+            todo
+            JOIN descendant ON todo.parent_todo_id = descendant.id
+    )
+    SELECT
+        id
+    FROM
+        descendant;
+$$ LANGUAGE SQL STABLE;
\ No newline at end of file
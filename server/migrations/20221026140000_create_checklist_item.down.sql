@@ -0,0 +1,5 @@
+DROP TRIGGER checklist_item_set_updated_at ON checklist_item;
+
+DROP INDEX checklist_item_by_todo_id;
+
+DROP TABLE checklist_item;
\ No newline at end of file
@@ -0,0 +1,15 @@
+CREATE TABLE checklist_item (
+    id BIGSERIAL PRIMARY KEY,
+    todo_id BIGINT NOT NULL REFERENCES todo(id) ON DELETE CASCADE,
+    position INT NOT NULL,
+    text TEXT NOT NULL,
+    checked BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX checklist_item_by_todo_id ON checklist_item (todo_id, position);
+
+CREATE TRIGGER checklist_item_set_updated_at BEFORE
+UPDATE
+    ON checklist_item FOR EACH ROW EXECUTE FUNCTION set_updated_at();
\ No newline at end of file
@@ -0,0 +1,3 @@
+DROP INDEX todo_dependency_by_blocked_by_todo_id;
+
+DROP TABLE todo_dependency;
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- `todo_id` cannot be worked on until `blocked_by_todo_id` is completed.
+CREATE TABLE todo_dependency (
+    todo_id BIGINT NOT NULL REFERENCES todo(id) ON DELETE CASCADE,
+    blocked_by_todo_id BIGINT NOT NULL REFERENCES todo(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    PRIMARY KEY (todo_id, blocked_by_todo_id),
+    CHECK (todo_id <> blocked_by_todo_id)
+);
+
+CREATE INDEX todo_dependency_by_blocked_by_todo_id ON todo_dependency (blocked_by_todo_id);
\ No newline at end of file
@@ -0,0 +1,7 @@
+DROP INDEX todo_by_list_sort_rank;
+
+ALTER TABLE
+    todo DROP COLUMN sort_rank;
+
+ALTER TABLE
+    todo DROP COLUMN priority;
\ No newline at end of file
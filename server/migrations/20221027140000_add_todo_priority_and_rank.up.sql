@@ -0,0 +1,34 @@
+ALTER TABLE
+    todo
+ADD
+    COLUMN priority SMALLINT NOT NULL DEFAULT 0 CHECK (priority BETWEEN 0 AND 4);
+
+-- Manual position of a todo within its list, which is its project or, for
+-- todos without a project, the inbox of the account. Ranks are spaced apart so
+-- that moving a todo usually only rewrites the todo itself.
+ALTER TABLE
+    todo
+ADD
+    COLUMN sort_rank BIGINT NOT NULL DEFAULT 0;
+
+UPDATE
+    todo
+SET
+    sort_rank = ranked.rank
+FROM
+    (
+        SELECT
+            id,
+            ROW_NUMBER() OVER (
+                PARTITION BY account_id,
+                project_id
+                ORDER BY
+                    id
+            ) * 65536 AS rank
+        FROM
+            todo
+    ) ranked
+WHERE
+    todo.id = ranked.id;
+
+CREATE INDEX todo_by_list_sort_rank ON todo (account_id, project_id, sort_rank);
\ No newline at end of file
@@ -0,0 +1,11 @@
+ALTER TABLE
+    todo DROP COLUMN scheduled_date;
+
+ALTER TABLE
+    todo DROP COLUMN start_date;
+
+ALTER TABLE
+    todo DROP CONSTRAINT todo_single_deadline;
+
+ALTER TABLE
+    todo DROP COLUMN deadline_date;
\ No newline at end of file
@@ -0,0 +1,26 @@
+-- A deadline without a time of day, due by the end of the day. A todo has
+-- either a timed deadline or a date-only deadline, never both.
+ALTER TABLE
+    todo
+ADD
+    COLUMN deadline_date DATE;
+
+ALTER TABLE
+    todo
+ADD
+    CONSTRAINT todo_single_deadline CHECK (
+        deadline IS NULL
+        OR deadline_date IS NULL
+    );
+
+-- The todo is deferred, and not available to work on, before this date.
+ALTER TABLE
+    todo
+ADD
+    COLUMN start_date DATE;
+
+-- The date the todo is planned to be worked on.
+ALTER TABLE
+    todo
+ADD
+    COLUMN scheduled_date DATE;
\ No newline at end of file
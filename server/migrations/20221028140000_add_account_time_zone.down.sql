@@ -0,0 +1,6 @@
+DROP FUNCTION local_today;
+
+DROP FUNCTION local_day_start;
+
+ALTER TABLE
+    account DROP COLUMN time_zone;
\ No newline at end of file
@@ -0,0 +1,18 @@
+-- IANA name of the time zone of the user, such as `Europe/Berlin`. Dates
+-- without a time, and "today", are interpreted in this zone.
+ALTER TABLE
+    account
+ADD
+    COLUMN time_zone TEXT NOT NULL DEFAULT 'UTC';
+
+-- The UTC time at which the given date starts in the given time zone.
+CREATE FUNCTION local_day_start(day DATE, time_zone TEXT) RETURNS TIMESTAMP AS $$
+    SELECT
+        (day::TIMESTAMP AT TIME ZONE time_zone) AT TIME ZONE 'UTC';
+$$ LANGUAGE SQL STABLE;
+
+-- The current date in the given time zone.
+CREATE FUNCTION local_today(time_zone TEXT) RETURNS DATE AS $$
+    SELECT
+        (CURRENT_TIMESTAMP AT TIME ZONE time_zone)::DATE;
+$$ LANGUAGE SQL STABLE;
\ No newline at end of file
@@ -0,0 +1,8 @@
+ALTER TABLE
+    todo DROP COLUMN previous_occurrence_todo_id;
+
+ALTER TABLE
+    todo DROP COLUMN recurs_from_completion;
+
+ALTER TABLE
+    todo DROP COLUMN recurrence;
\ No newline at end of file
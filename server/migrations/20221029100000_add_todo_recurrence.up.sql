@@ -0,0 +1,22 @@
+-- An RFC 5545 recurrence rule, such as `FREQ=WEEKLY;BYDAY=MO,TH`. Completing a
+-- recurring todo creates its next occurrence.
+ALTER TABLE
+    todo
+ADD
+    COLUMN recurrence TEXT;
+
+-- Whether the next occurrence is scheduled relative to the completion of the
+-- todo, such as every 3 days after completion, instead of following the rule.
+ALTER TABLE
+    todo
+ADD
+    COLUMN recurs_from_completion BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The occurrence this todo was created from. Each occurrence has at most one
+-- next occurrence.
+ALTER TABLE
+    todo
+ADD
+    COLUMN previous_occurrence_todo_id BIGINT UNIQUE REFERENCES todo(id) ON DELETE
+SET
+    NULL;
\ No newline at end of file
@@ -0,0 +1,9 @@
+DROP TABLE notification;
+
+DROP FUNCTION reminder_fire_at;
+
+DROP TABLE reminder;
+
+ALTER TABLE
+    account DROP COLUMN webhook_url,
+    DROP COLUMN email;
\ No newline at end of file
@@ -0,0 +1,67 @@
+-- Delivery addresses for reminders sent by email or webhook.
+ALTER TABLE
+    account
+ADD
+    COLUMN email TEXT,
+ADD
+    COLUMN webhook_url TEXT;
+
+CREATE TABLE reminder (
+    id BIGSERIAL PRIMARY KEY,
+    todo_id BIGINT NOT NULL REFERENCES todo(id) ON DELETE CASCADE,
+    -- Either an absolute time, or a time relative to the deadline of the todo.
+    remind_at TIMESTAMP,
+    minutes_before_deadline INT,
+    channel TEXT NOT NULL CHECK (channel IN ('in_app', 'email', 'webhook')),
+    sent_at TIMESTAMP,
+    -- Failed deliveries are retried with a backoff until they succeed or run
+    -- out of attempts.
+    attempts INT NOT NULL DEFAULT 0,
+    retry_at TIMESTAMP,
+    last_error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    CHECK ((remind_at IS NULL) <> (minutes_before_deadline IS NULL))
+);
+
+CREATE INDEX reminder_by_todo_id ON reminder (todo_id);
+
+CREATE INDEX reminder_unsent ON reminder (id)
+WHERE
+    sent_at IS NULL;
+
+CREATE TRIGGER reminder_set_updated_at BEFORE
+UPDATE
+    ON reminder FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+-- The UTC time a reminder is due, or NULL for a reminder relative to the
+-- deadline of a todo without a deadline. Date-only deadlines start at the
+-- beginning of the day in the time zone of the user.
+CREATE FUNCTION reminder_fire_at(reminder_id BIGINT) RETURNS TIMESTAMP AS $$
+    SELECT
+        COALESCE(
+            reminder.remind_at,
+            COALESCE(
+                todo.deadline,
+                local_day_start(todo.deadline_date, account.time_zone)
+            ) - reminder.minutes_before_deadline * INTERVAL '1 minute'
+        )
+    FROM
+        reminder
+        JOIN todo ON todo.id = reminder.todo_id
+        JOIN account ON account.id = todo.account_id
+    WHERE
+        reminder.id = reminder_id;
+$$ LANGUAGE SQL STABLE;
+
+-- Notifications shown inside the app.
+CREATE TABLE notification (
+    id BIGSERIAL PRIMARY KEY,
+    account_id INTEGER NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    todo_id BIGINT REFERENCES todo(id) ON DELETE CASCADE,
+    message TEXT NOT NULL,
+    read_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX notification_by_account_id ON notification (account_id, created_at);
\ No newline at end of file
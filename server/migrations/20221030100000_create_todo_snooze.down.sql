@@ -0,0 +1,5 @@
+DROP TABLE todo_snooze;
+
+ALTER TABLE
+    todo DROP COLUMN snooze_count,
+    DROP COLUMN snoozed_until;
\ No newline at end of file
@@ -0,0 +1,26 @@
+-- A snoozed todo is hidden from the default todo list until this time.
+ALTER TABLE
+    todo
+ADD
+    COLUMN snoozed_until TIMESTAMP,
+ADD
+    COLUMN snooze_count INT NOT NULL DEFAULT 0;
+
+CREATE TABLE todo_snooze (
+    id BIGSERIAL PRIMARY KEY,
+    todo_id BIGINT NOT NULL REFERENCES todo(id) ON DELETE CASCADE,
+    snoozed_until TIMESTAMP NOT NULL,
+    -- The deadline before and after snoozing, if the snooze moved it.
+    previous_deadline TIMESTAMP,
+    previous_deadline_date DATE,
+    deadline TIMESTAMP,
+    deadline_date DATE,
+    -- Whether the user is notified by a reminder when the snooze ends.
+    notify BOOLEAN NOT NULL DEFAULT FALSE,
+    reminder_id BIGINT REFERENCES reminder(id) ON DELETE SET NULL,
+    -- Set when the todo is woken up, or snoozed again, before the snooze ends.
+    cancelled_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX todo_snooze_by_todo_id ON todo_snooze (todo_id, created_at);
\ No newline at end of file
@@ -0,0 +1,20 @@
+DROP TRIGGER todo_record_state_transition ON todo;
+
+DROP FUNCTION record_todo_state_transition;
+
+DROP TABLE todo_state_transition;
+
+DROP TRIGGER todo_sync_workflow_state ON todo;
+
+DROP FUNCTION sync_todo_workflow_state;
+
+ALTER TABLE
+    todo DROP COLUMN workflow_state_id;
+
+DROP TRIGGER project_create_workflow_states ON project;
+
+DROP FUNCTION create_project_workflow_states;
+
+DROP FUNCTION create_default_workflow_states;
+
+DROP TABLE workflow_state;
\ No newline at end of file
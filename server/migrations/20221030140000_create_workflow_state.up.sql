@@ -0,0 +1,160 @@
+-- The states a todo of a project moves through, such as Backlog, In progress,
+-- and Done. The category of a state decides whether its todos are completed.
+CREATE TABLE workflow_state (
+    id BIGSERIAL PRIMARY KEY,
+    project_id BIGINT NOT NULL REFERENCES project(id) ON DELETE CASCADE,
+    state_name TEXT NOT NULL,
+    category TEXT NOT NULL CHECK (category IN ('open', 'active', 'closed')),
+    position INT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    UNIQUE (project_id, state_name)
+);
+
+CREATE TRIGGER workflow_state_set_updated_at BEFORE
+UPDATE
+    ON workflow_state FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+CREATE FUNCTION create_default_workflow_states(target_project_id BIGINT) RETURNS VOID AS $$
+    INSERT INTO
+        workflow_state (project_id, state_name, category, position)
+    VALUES
+        (target_project_id, 'Backlog', 'open', 0),
+        (target_project_id, 'In progress', 'active', 1),
+        (target_project_id, 'In review', 'active', 2),
+        (target_project_id, 'Done', 'closed', 3),
+        (target_project_id, 'Won''t do', 'closed', 4);
+$$ LANGUAGE SQL;
+
+SELECT
+    create_default_workflow_states(id)
+FROM
+    project;
+
+CREATE FUNCTION create_project_workflow_states() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM create_default_workflow_states(NEW.id);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER project_create_workflow_states
+AFTER
+INSERT
+    ON project FOR EACH ROW EXECUTE FUNCTION create_project_workflow_states();
+
+ALTER TABLE
+    todo
+ADD
+    COLUMN workflow_state_id BIGINT REFERENCES workflow_state(id);
+
+CREATE INDEX todo_by_workflow_state_id ON todo (workflow_state_id);
+
+-- Keeps the workflow state and `completed_at` of a todo consistent. Moving a
+-- todo to a state completes or reopens it according to the category of the
+-- state, while completing or reopening a todo moves it to the first state of
+-- the matching category in its project.
+CREATE FUNCTION sync_todo_workflow_state() RETURNS TRIGGER AS $$
+DECLARE
+    state_category TEXT;
+BEGIN
+    IF NEW.project_id IS NULL THEN
+        NEW.workflow_state_id := NULL;
+        RETURN NEW;
+    END IF;
+
+    SELECT
+        category INTO state_category
+    FROM
+        workflow_state
+    WHERE
+        id = NEW.workflow_state_id
+        AND project_id = NEW.project_id;
+
+    IF state_category IS NULL THEN
+        -- The state is missing, or belongs to the previous project of the todo.
+        NEW.workflow_state_id := NULL;
+    ELSIF TG_OP = 'INSERT'
+    OR NEW.workflow_state_id IS DISTINCT FROM OLD.workflow_state_id THEN
+        IF state_category = 'closed' THEN
+            NEW.completed_at := COALESCE(NEW.completed_at, CURRENT_TIMESTAMP AT TIME ZONE 'UTC');
+        ELSE
+            NEW.completed_at := NULL;
+        END IF;
+        RETURN NEW;
+    END IF;
+
+    IF NEW.workflow_state_id IS NULL
+    OR (state_category = 'closed') <> (NEW.completed_at IS NOT NULL) THEN
+        NEW.workflow_state_id := (
+            SELECT
+                id
+            FROM
+                workflow_state
+            WHERE
+                project_id = NEW.project_id
+                AND (category = 'closed') = (NEW.completed_at IS NOT NULL)
+            ORDER BY
+                category = 'open' DESC,
+                position,
+                id
+            LIMIT
+                1
+        );
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER todo_sync_workflow_state BEFORE
+INSERT
+    OR
+UPDATE
+    ON todo FOR EACH ROW EXECUTE FUNCTION sync_todo_workflow_state();
+
+-- Every change of the workflow state of a todo, for computing cycle times.
+CREATE TABLE todo_state_transition (
+    id BIGSERIAL PRIMARY KEY,
+    todo_id BIGINT NOT NULL REFERENCES todo(id) ON DELETE CASCADE,
+    from_workflow_state_id BIGINT REFERENCES workflow_state(id) ON DELETE SET NULL,
+    to_workflow_state_id BIGINT REFERENCES workflow_state(id) ON DELETE SET NULL,
+    transitioned_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX todo_state_transition_by_todo_id ON todo_state_transition (todo_id, transitioned_at);
+
+CREATE FUNCTION record_todo_state_transition() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        IF NEW.workflow_state_id IS NOT NULL THEN
+            INSERT INTO
+                todo_state_transition (todo_id, to_workflow_state_id)
+            VALUES
+                (NEW.id, NEW.workflow_state_id);
+        END IF;
+    ELSIF NEW.workflow_state_id IS DISTINCT FROM OLD.workflow_state_id THEN
+        INSERT INTO
+            todo_state_transition (todo_id, from_workflow_state_id, to_workflow_state_id)
+        VALUES
+            (NEW.id, OLD.workflow_state_id, NEW.workflow_state_id);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Not limited to updates of `workflow_state_id`, since the state also changes
+-- when `todo_sync_workflow_state` follows a change of `completed_at`.
+CREATE TRIGGER todo_record_state_transition
+AFTER
+INSERT
+    OR
+UPDATE
+    ON todo FOR EACH ROW EXECUTE FUNCTION record_todo_state_transition();
+
+-- Existing todos start in the first open or closed state of their project.
+UPDATE
+    todo
+SET
+    workflow_state_id = NULL
+WHERE
+    project_id IS NOT NULL;
\ No newline at end of file
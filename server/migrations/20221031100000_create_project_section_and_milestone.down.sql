@@ -0,0 +1,11 @@
+DROP TRIGGER todo_clear_project_groupings ON todo;
+
+DROP FUNCTION clear_todo_project_groupings;
+
+ALTER TABLE
+    todo DROP COLUMN milestone_id,
+    DROP COLUMN section_id;
+
+DROP TABLE milestone;
+
+DROP TABLE project_section;
\ No newline at end of file
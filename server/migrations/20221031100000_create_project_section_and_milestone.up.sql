@@ -0,0 +1,82 @@
+-- Headings that group the todos of a project.
+CREATE TABLE project_section (
+    id BIGSERIAL PRIMARY KEY,
+    project_id BIGINT NOT NULL REFERENCES project(id) ON DELETE CASCADE,
+    section_name TEXT NOT NULL,
+    position INT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX project_section_by_project_id ON project_section (project_id, position);
+
+CREATE TRIGGER project_section_set_updated_at BEFORE
+UPDATE
+    ON project_section FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+-- Goals of a project, whose progress is computed from the todos assigned to
+-- them.
+CREATE TABLE milestone (
+    id BIGSERIAL PRIMARY KEY,
+    project_id BIGINT NOT NULL REFERENCES project(id) ON DELETE CASCADE,
+    milestone_name TEXT NOT NULL,
+    target_date DATE,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX milestone_by_project_id ON milestone (project_id);
+
+CREATE TRIGGER milestone_set_updated_at BEFORE
+UPDATE
+    ON milestone FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+ALTER TABLE
+    todo
+ADD
+    COLUMN section_id BIGINT REFERENCES project_section(id) ON DELETE SET NULL,
+ADD
+    COLUMN milestone_id BIGINT REFERENCES milestone(id) ON DELETE SET NULL;
+
+CREATE INDEX todo_by_section_id ON todo (section_id);
+
+CREATE INDEX todo_by_milestone_id ON todo (milestone_id);
+
+-- Removes a todo from the section and milestone of its previous project when
+-- it moves to another project.
+CREATE FUNCTION clear_todo_project_groupings() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.section_id IS NOT NULL
+    AND NOT EXISTS (
+        SELECT
+            1
+        FROM
+            project_section
+        WHERE
+            id = NEW.section_id
+            AND project_id = NEW.project_id
+    ) THEN
+        NEW.section_id := NULL;
+    END IF;
+
+    IF NEW.milestone_id IS NOT NULL
+    AND NOT EXISTS (
+        SELECT
+            1
+        FROM
+            milestone
+        WHERE
+            id = NEW.milestone_id
+            AND project_id = NEW.project_id
+    ) THEN
+        NEW.milestone_id := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER todo_clear_project_groupings BEFORE
+INSERT
+    OR
+UPDATE
+    ON todo FOR EACH ROW EXECUTE FUNCTION clear_todo_project_groupings();
\ No newline at end of file
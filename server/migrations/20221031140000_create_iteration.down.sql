@@ -0,0 +1,14 @@
+DROP TRIGGER todo_record_iteration_todo ON todo;
+
+DROP FUNCTION record_iteration_todo;
+
+DROP TRIGGER todo_clear_iteration ON todo;
+
+DROP FUNCTION clear_todo_iteration;
+
+DROP TABLE iteration_todo;
+
+ALTER TABLE
+    todo DROP COLUMN iteration_id;
+
+DROP TABLE iteration;
\ No newline at end of file
@@ -0,0 +1,99 @@
+-- Time-boxed iterations, such as sprints, that the todos of a project are
+-- planned in.
+CREATE TABLE iteration (
+    id BIGSERIAL PRIMARY KEY,
+    project_id BIGINT NOT NULL REFERENCES project(id) ON DELETE CASCADE,
+    iteration_name TEXT NOT NULL,
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    CONSTRAINT iteration_dates_in_order CHECK (start_date <= end_date)
+);
+
+CREATE INDEX iteration_by_project_id ON iteration (project_id, start_date);
+
+CREATE TRIGGER iteration_set_updated_at BEFORE
+UPDATE
+    ON iteration FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+ALTER TABLE
+    todo
+ADD
+    COLUMN iteration_id BIGINT REFERENCES iteration(id) ON DELETE SET NULL;
+
+CREATE INDEX todo_by_iteration_id ON todo (iteration_id);
+
+-- The todos committed to an iteration. Todos carried over to a later
+-- iteration stay committed to the earlier one, while todos removed from an
+-- iteration otherwise are no longer committed to it.
+CREATE TABLE iteration_todo (
+    iteration_id BIGINT NOT NULL REFERENCES iteration(id) ON DELETE CASCADE,
+    todo_id BIGINT NOT NULL REFERENCES todo(id) ON DELETE CASCADE,
+    assigned_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    carried_over_at TIMESTAMP,
+    PRIMARY KEY (iteration_id, todo_id)
+);
+
+CREATE INDEX iteration_todo_by_todo_id ON iteration_todo (todo_id);
+
+-- Removes a todo from the iteration of its previous project when it moves to
+-- another project.
+CREATE FUNCTION clear_todo_iteration() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.iteration_id IS NOT NULL
+    AND NOT EXISTS (
+        SELECT
+            1
+        FROM
+            iteration
+        WHERE
+            id = NEW.iteration_id
+            AND project_id = NEW.project_id
+    ) THEN
+        NEW.iteration_id := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER todo_clear_iteration BEFORE
+INSERT
+    OR
+UPDATE
+    ON todo FOR EACH ROW EXECUTE FUNCTION clear_todo_iteration();
+
+CREATE FUNCTION record_iteration_todo() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW.iteration_id IS NOT DISTINCT FROM OLD.iteration_id THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP = 'UPDATE' AND OLD.iteration_id IS NOT NULL THEN
+        DELETE FROM
+            iteration_todo
+        WHERE
+            iteration_id = OLD.iteration_id
+            AND todo_id = OLD.id
+            AND carried_over_at IS NULL;
+    END IF;
+
+    IF NEW.iteration_id IS NOT NULL THEN
+        INSERT INTO
+            iteration_todo (iteration_id, todo_id)
+        VALUES
+            (NEW.iteration_id, NEW.id) ON CONFLICT (iteration_id, todo_id) DO
+        UPDATE
+        SET
+            carried_over_at = NULL;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER todo_record_iteration_todo
+AFTER
+INSERT
+    OR
+UPDATE
+    ON todo FOR EACH ROW EXECUTE FUNCTION record_iteration_todo();
\ No newline at end of file
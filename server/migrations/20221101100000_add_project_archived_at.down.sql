@@ -0,0 +1,2 @@
+ALTER TABLE
+    project DROP COLUMN archived_at;
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Archived projects and their todos are hidden from default listings and
+-- cannot be modified until the project is unarchived.
+ALTER TABLE
+    project
+ADD
+    COLUMN archived_at TIMESTAMP;
\ No newline at end of file
@@ -0,0 +1,5 @@
+DROP TRIGGER project_record_shortcode_alias ON project;
+
+DROP FUNCTION record_project_shortcode_alias;
+
+DROP TABLE project_shortcode_alias;
\ No newline at end of file
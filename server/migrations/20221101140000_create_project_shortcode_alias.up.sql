@@ -0,0 +1,37 @@
+-- The previous shortcodes of projects, so that references using them (such
+-- as `OLD-12`) keep resolving after a project is given a new shortcode.
+CREATE TABLE project_shortcode_alias (
+    account_id INT NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    shortcode TEXT NOT NULL,
+    project_id BIGINT NOT NULL REFERENCES project(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    PRIMARY KEY (account_id, shortcode)
+);
+
+CREATE INDEX project_shortcode_alias_by_project_id ON project_shortcode_alias(project_id);
+
+CREATE FUNCTION record_project_shortcode_alias() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.shortcode IS DISTINCT FROM OLD.shortcode THEN
+        -- A project taking back one of its previous shortcodes.
+        DELETE FROM
+            project_shortcode_alias
+        WHERE
+            account_id = NEW.account_id
+            AND shortcode = NEW.shortcode
+            AND project_id = NEW.id;
+
+        INSERT INTO
+            project_shortcode_alias (account_id, shortcode, project_id)
+        VALUES
+            (OLD.account_id, OLD.shortcode, OLD.id)
+        ON CONFLICT DO NOTHING;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER project_record_shortcode_alias
+AFTER
+UPDATE
+    OF shortcode ON project FOR EACH ROW EXECUTE FUNCTION record_project_shortcode_alias();
\ No newline at end of file
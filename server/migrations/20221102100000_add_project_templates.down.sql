@@ -0,0 +1,5 @@
+ALTER TABLE
+    project DROP COLUMN is_template;
+
+ALTER TABLE
+    project DROP COLUMN start_date;
\ No newline at end of file
@@ -0,0 +1,13 @@
+-- The date the project starts. The dates of the todos and milestones of a
+-- copy of the project are shifted relative to it.
+ALTER TABLE
+    project
+ADD
+    COLUMN start_date DATE;
+
+-- Templates are hidden from default listings and are copied to create new
+-- projects with the same todos.
+ALTER TABLE
+    project
+ADD
+    COLUMN is_template BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
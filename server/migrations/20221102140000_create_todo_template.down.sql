@@ -0,0 +1,3 @@
+DROP TABLE todo_template_tag;
+
+DROP TABLE todo_template;
\ No newline at end of file
@@ -0,0 +1,28 @@
+-- Reusable todos. Placeholders such as `{{date}}` in the title and memo are
+-- filled in when a todo is created from the template.
+CREATE TABLE todo_template (
+    id BIGSERIAL PRIMARY KEY,
+    account_id INT NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+    template_name TEXT NOT NULL,
+    title TEXT NOT NULL,
+    memo TEXT NOT NULL DEFAULT '',
+    priority SMALLINT NOT NULL DEFAULT 0,
+    project_id BIGINT REFERENCES project(id) ON DELETE SET NULL,
+    -- The created todo is due this many days after the date it is created for.
+    deadline_in_days INT,
+    -- The texts of the checklist items of the created todo.
+    checklist TEXT [] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+    UNIQUE (account_id, template_name)
+);
+
+CREATE TRIGGER todo_template_set_updated_at BEFORE
+UPDATE
+    ON todo_template FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+CREATE TABLE todo_template_tag (
+    template_id BIGINT NOT NULL REFERENCES todo_template(id) ON DELETE CASCADE,
+    tag_id BIGINT NOT NULL REFERENCES tag(id) ON DELETE CASCADE,
+    PRIMARY KEY (template_id, tag_id)
+);
\ No newline at end of file
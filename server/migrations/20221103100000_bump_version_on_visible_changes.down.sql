@@ -0,0 +1,6 @@
+CREATE OR REPLACE FUNCTION increment_version() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
\ No newline at end of file
@@ -0,0 +1,3 @@
+DROP TRIGGER project_check_shortcode_alias ON project;
+
+DROP FUNCTION check_project_shortcode_alias;
\ No newline at end of file
@@ -0,0 +1,36 @@
+-- A project cannot take a previous shortcode of another project, whose old
+-- references must keep resolving to that project. The check is serialized
+-- per account with the recording of aliases, so that a concurrent rename
+-- cannot free up a shortcode and alias it in between.
+CREATE FUNCTION check_project_shortcode_alias() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR NEW.shortcode IS DISTINCT FROM OLD.shortcode THEN
+        PERFORM pg_advisory_xact_lock(
+            'project_shortcode_alias'::regclass::oid::INT,
+            NEW.account_id
+        );
+
+        IF EXISTS (
+            SELECT
+                1
+            FROM
+                project_shortcode_alias
+            WHERE
+                account_id = NEW.account_id
+                AND shortcode = NEW.shortcode
+                AND project_id <> NEW.id
+        ) THEN
+            RAISE EXCEPTION 'The shortcode % was previously used by another project.', NEW.shortcode
+                USING ERRCODE = 'unique_violation',
+                CONSTRAINT = 'project_shortcode_alias_pkey';
+        END IF;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER project_check_shortcode_alias BEFORE
+INSERT
+    OR
+UPDATE
+    OF shortcode ON project FOR EACH ROW EXECUTE FUNCTION check_project_shortcode_alias();
\ No newline at end of file
@@ -0,0 +1,13 @@
+CREATE OR REPLACE FUNCTION increment_version() RETURNS TRIGGER AS $$
+BEGIN
+    -- Maintenance writes, such as touching `updated_at` or rebalancing
+    -- `sort_rank`, do not change what clients see and keep the version.
+    IF to_jsonb(NEW) - ARRAY['version', 'updated_at', 'sort_rank']
+    IS DISTINCT FROM to_jsonb(OLD) - ARRAY['version', 'updated_at', 'sort_rank'] THEN
+        NEW.version := OLD.version + 1;
+    ELSE
+        NEW.version := OLD.version;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
\ No newline at end of file
@@ -0,0 +1,15 @@
+-- `sort_rank` is part of the public representation of todos, so reordering
+-- them must change their version as well.
+CREATE OR REPLACE FUNCTION increment_version() RETURNS TRIGGER AS $$
+BEGIN
+    -- Touching `updated_at` alone does not change what clients see and keeps
+    -- the version.
+    IF to_jsonb(NEW) - ARRAY['version', 'updated_at']
+    IS DISTINCT FROM to_jsonb(OLD) - ARRAY['version', 'updated_at'] THEN
+        NEW.version := OLD.version + 1;
+    ELSE
+        NEW.version := OLD.version;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
\ No newline at end of file
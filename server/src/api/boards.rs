@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+
+use crate::auth::AccountId;
+
+use super::{
+    ordering::{place_todo, MoveTodoRequest},
+    tags::validate_account_has_tag,
+    todos::{
+        fetch_project_todos, fetch_todo, handle_todo_completed, lock_todo_for_completion,
+        todo_response, validate_account_can_edit_project, validate_account_has_project,
+        validate_priority, OpenSubtasksPolicy, PublicTodo,
+    },
+    workflow_states::{validate_workflow_state, StateCategory},
+    APIResponse, APIResult, ErrorResponse,
+};
+
+/// How the todos of a project are grouped into the columns of its board.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardGrouping {
+    /// One column per workflow state, in the order of the states.
+    #[default]
+    State,
+    /// One column per priority, from the highest priority to no priority.
+    Priority,
+    /// One column per tag attached to todos of the project, and a column for
+    /// untagged todos. Todos with several tags appear in several columns.
+    Tag,
+}
+
+impl BoardGrouping {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BoardGrouping::State => "state",
+            BoardGrouping::Priority => "priority",
+            BoardGrouping::Tag => "tag",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PublicBoard {
+    project_id: i64,
+    group_by: BoardGrouping,
+    columns: Vec<PublicBoardColumn>,
+}
+
+#[derive(Serialize)]
+pub struct PublicBoardColumn {
+    /// The workflow state id, priority, or tag id of the column. `None` for
+    /// the column of untagged todos.
+    key: Option<i64>,
+    title: String,
+    /// The category of the workflow state, when grouping by state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<StateCategory>,
+    /// The todos in the column, in their manual order.
+    todos: Vec<PublicTodo>,
+}
+
+impl PublicBoardColumn {
+    fn new(key: Option<i64>, title: String, category: Option<StateCategory>) -> Self {
+        PublicBoardColumn {
+            key,
+            title,
+            category,
+            todos: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GetBoardQuery {
+    #[serde(default)]
+    group_by: BoardGrouping,
+}
+
+/// Moves a todo to another column of the board and to a position within the
+/// column, in one step. The neighbours must be in the target column.
+#[derive(Deserialize)]
+pub struct MoveBoardTodoRequest {
+    todo_id: i64,
+    #[serde(default)]
+    group_by: BoardGrouping,
+    /// The column the todo is dragged from, only needed when grouping by tag
+    /// to know which tag to replace.
+    from_column: Option<i64>,
+    /// The column the todo is dragged to. `None` moves the todo to the column
+    /// of untagged todos, removing all of its tags.
+    to_column: Option<i64>,
+    #[serde(flatten)]
+    position: MoveTodoRequest,
+    /// What to do with open subtasks when the todo is moved to a closed state.
+    #[serde(default)]
+    open_subtasks: OpenSubtasksPolicy,
+}
+
+fn map_board_move_error(_err: sqlx::Error) -> ErrorResponse {
+    ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to move todo.")
+}
+
+fn priority_title(priority: i64) -> String {
+    if priority == 0 {
+        "No priority".to_string()
+    } else {
+        format!("Priority {}", priority)
+    }
+}
+
+async fn fetch_columns(
+    pg_pool: &PgPool,
+    project_id: i64,
+    group_by: BoardGrouping,
+) -> APIResult<Vec<PublicBoardColumn>> {
+    let map_err = |_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch board columns.",
+        )
+    };
+
+    Ok(match group_by {
+        BoardGrouping::State => sqlx::query!(
+            "
+                SELECT id, state_name, category FROM workflow_state
+                WHERE project_id = $1
+                ORDER BY position, id
+            ",
+            project_id
+        )
+        .fetch_all(pg_pool)
+        .await
+        .map_err(map_err)?
+        .into_iter()
+        .map(|record| {
+            let category = match record.category.as_str() {
+                "closed" => StateCategory::Closed,
+                "active" => StateCategory::Active,
+                _ => StateCategory::Open,
+            };
+            PublicBoardColumn::new(Some(record.id), record.state_name, Some(category))
+        })
+        .collect(),
+        BoardGrouping::Priority => (0..=4)
+            .rev()
+            .map(|priority| PublicBoardColumn::new(Some(priority), priority_title(priority), None))
+            .collect(),
+        BoardGrouping::Tag => sqlx::query!(
+            "
+                SELECT DISTINCT tag.id, tag_path(tag.id) AS \"path!\" FROM todo_tag
+                JOIN todo ON todo.id = todo_tag.todo_id
+                JOIN tag ON tag.id = todo_tag.tag_id
+                WHERE todo.project_id = $1
+                ORDER BY 2
+            ",
+            project_id
+        )
+        .fetch_all(pg_pool)
+        .await
+        .map_err(map_err)?
+        .into_iter()
+        .map(|record| PublicBoardColumn::new(Some(record.id), record.path, None))
+        .chain(std::iter::once(PublicBoardColumn::new(
+            None,
+            "Untagged".to_string(),
+            None,
+        )))
+        .collect(),
+    })
+}
+
+pub async fn get_board(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Query(query): Query<GetBoardQuery>,
+) -> APIResponse<PublicBoard> {
+    validate_account_has_project(&pg_pool, account_id, project_id).await?;
+
+    let mut columns = fetch_columns(&pg_pool, project_id, query.group_by).await?;
+    let todos = fetch_project_todos(&pg_pool, project_id).await?;
+
+    let placements = sqlx::query!(
+        "
+            SELECT
+                todo.id,
+                CASE $2
+                    WHEN 'state' THEN todo.workflow_state_id
+                    WHEN 'priority' THEN todo.priority::BIGINT
+                    ELSE todo_tag.tag_id
+                END AS column_key
+            FROM todo
+            LEFT JOIN todo_tag ON $2 = 'tag' AND todo_tag.todo_id = todo.id
+            WHERE todo.project_id = $1
+            ORDER BY todo.sort_rank, todo.id
+        ",
+        project_id,
+        query.group_by.as_str()
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch board.")
+    })?;
+
+    let column_index_by_key = columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| (column.key, index))
+        .collect::<HashMap<_, _>>();
+
+    for placement in placements {
+        // Todos with several tags are placed in several columns.
+        if let (Some(todo), Some(index)) = (
+            todos.get(&placement.id),
+            column_index_by_key.get(&placement.column_key),
+        ) {
+            columns[*index].todos.push(todo.clone());
+        }
+    }
+
+    Ok(PublicBoard {
+        project_id,
+        group_by: query.group_by,
+        columns,
+    }
+    .into())
+}
+
+/// Whether a todo is in the given column of the board.
+async fn column_contains(
+    transaction: &mut Transaction<'_, Postgres>,
+    group_by: BoardGrouping,
+    column: Option<i64>,
+    todo_id: i64,
+) -> APIResult<bool> {
+    sqlx::query!(
+        "
+            SELECT EXISTS (
+                SELECT 1 FROM todo
+                LEFT JOIN todo_tag ON $2 = 'tag' AND todo_tag.todo_id = todo.id
+                WHERE todo.id = $1 AND CASE $2
+                    WHEN 'state' THEN todo.workflow_state_id
+                    WHEN 'priority' THEN todo.priority::BIGINT
+                    ELSE todo_tag.tag_id
+                END IS NOT DISTINCT FROM $3
+            ) AS \"contains!\"
+        ",
+        todo_id,
+        group_by.as_str(),
+        column
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map(|record| record.contains)
+    .map_err(map_board_move_error)
+}
+
+pub async fn post_board_move(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Json(req): Json<MoveBoardTodoRequest>,
+) -> APIResponse<PublicTodo> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let in_project = sqlx::query!(
+        "
+            SELECT EXISTS (
+                SELECT 1 FROM todo WHERE id = $1 AND account_id = $2 AND project_id = $3
+            ) AS \"in_project!\"
+        ",
+        req.todo_id,
+        account_id,
+        project_id
+    )
+    .fetch_one(&pg_pool)
+    .await
+    .map(|record| record.in_project)
+    .map_err(map_board_move_error)?;
+
+    if !in_project {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The todo is not in the project.",
+        ));
+    }
+
+    let require_column = || {
+        req.to_column.ok_or_else(|| {
+            ErrorResponse::from(
+                StatusCode::BAD_REQUEST,
+                "Only todos grouped by tag can be moved to a column without a key.",
+            )
+        })
+    };
+    match req.group_by {
+        BoardGrouping::State => {
+            validate_workflow_state(&pg_pool, require_column()?, Some(project_id)).await?
+        }
+        BoardGrouping::Priority => {
+            validate_priority(i16::try_from(require_column()?).unwrap_or(i16::MAX))?
+        }
+        BoardGrouping::Tag => {
+            if let Some(tag_id) = req.to_column {
+                validate_account_has_tag(&pg_pool, account_id, tag_id).await?;
+            }
+        }
+    }
+
+    let mut transaction = pg_pool.begin().await.map_err(map_board_move_error)?;
+
+    // Take the lock of the account before the lock of the todo row, in the
+    // same order as the other updates of todos that take both.
+    sqlx::query!("SELECT pg_advisory_xact_lock($1)", i64::from(account_id))
+        .execute(&mut transaction)
+        .await
+        .map_err(map_board_move_error)?;
+
+    match req.group_by {
+        BoardGrouping::State => {
+            let was_completed = lock_todo_for_completion(&mut transaction, req.todo_id).await?;
+
+            let is_completed = sqlx::query!(
+                "
+                    UPDATE todo SET workflow_state_id = $2 WHERE id = $1
+                    RETURNING completed_at IS NOT NULL AS \"completed!\"
+                ",
+                req.todo_id,
+                req.to_column
+            )
+            .fetch_one(&mut transaction)
+            .await
+            .map(|record| record.completed)
+            .map_err(map_board_move_error)?;
+
+            if !was_completed && is_completed {
+                handle_todo_completed(&mut transaction, req.todo_id, req.open_subtasks).await?;
+            }
+        }
+        BoardGrouping::Priority => {
+            sqlx::query!(
+                "UPDATE todo SET priority = $2::BIGINT::SMALLINT WHERE id = $1",
+                req.todo_id,
+                req.to_column
+            )
+            .execute(&mut transaction)
+            .await
+            .map_err(map_board_move_error)?;
+        }
+        BoardGrouping::Tag if req.from_column != req.to_column => {
+            // Moving to the untagged column removes every tag, otherwise only
+            // the tag of the column the todo was dragged from is replaced.
+            sqlx::query!(
+                "
+                    DELETE FROM todo_tag
+                    WHERE todo_id = $1 AND ($2::BIGINT IS NULL OR tag_id = $3)
+                ",
+                req.todo_id,
+                req.to_column,
+                req.from_column
+            )
+            .execute(&mut transaction)
+            .await
+            .map_err(map_board_move_error)?;
+
+            if let Some(tag_id) = req.to_column {
+                sqlx::query!(
+                    "
+                        INSERT INTO todo_tag (todo_id, tag_id)
+                        VALUES ($1, $2)
+                        ON CONFLICT DO NOTHING
+                    ",
+                    req.todo_id,
+                    tag_id
+                )
+                .execute(&mut transaction)
+                .await
+                .map_err(map_board_move_error)?;
+            }
+
+            sqlx::query!(
+                "
+                    UPDATE todo
+                    SET updated_at = CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+                    WHERE id = $1
+                ",
+                req.todo_id
+            )
+            .execute(&mut transaction)
+            .await
+            .map_err(map_board_move_error)?;
+        }
+        BoardGrouping::Tag => {}
+    }
+
+    for neighbour_id in [req.position.after_todo_id, req.position.before_todo_id]
+        .into_iter()
+        .flatten()
+    {
+        if !column_contains(&mut transaction, req.group_by, req.to_column, neighbour_id).await? {
+            return Err(ErrorResponse::from(
+                StatusCode::BAD_REQUEST,
+                "The todo can only be placed next to other todos in the target column.",
+            ));
+        }
+    }
+
+    place_todo(&mut transaction, account_id, req.todo_id, &req.position).await?;
+
+    transaction.commit().await.map_err(map_board_move_error)?;
+
+    let record = fetch_todo(&pg_pool, account_id, req.todo_id).await?;
+
+    todo_response(&pg_pool, record).await
+}
@@ -0,0 +1,311 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+use crate::auth::AccountId;
+
+use super::{
+    todos::{touch_todo, validate_account_can_edit_todo, validate_account_has_todo},
+    APIResponse, APIResult, ErrorResponse,
+};
+
+#[derive(Serialize)]
+pub struct PublicChecklistItem {
+    id: i64,
+    todo_id: i64,
+    position: i32,
+    text: String,
+    checked: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct ChecklistItemRecord {
+    id: i64,
+    todo_id: i64,
+    position: i32,
+    text: String,
+    checked: bool,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl From<ChecklistItemRecord> for PublicChecklistItem {
+    fn from(record: ChecklistItemRecord) -> Self {
+        PublicChecklistItem {
+            id: record.id,
+            todo_id: record.todo_id,
+            position: record.position,
+            text: record.text,
+            checked: record.checked,
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+/// The number of checklist items and checked checklist items of a todo.
+#[derive(Clone, Copy, Default)]
+pub(super) struct ChecklistCounts {
+    pub item_count: i64,
+    pub checked_item_count: i64,
+}
+
+/// Fetches the checklist counts of each of the given todos.
+pub(super) async fn fetch_checklist_counts_by_todo_id(
+    pg_pool: &PgPool,
+    todo_ids: &[i64],
+) -> APIResult<HashMap<i64, ChecklistCounts>> {
+    Ok(sqlx::query!(
+        "
+            SELECT
+                todo_id,
+                COUNT(*) AS \"item_count!\",
+                COUNT(*) FILTER (WHERE checked) AS \"checked_item_count!\"
+            FROM checklist_item
+            WHERE todo_id = ANY($1)
+            GROUP BY todo_id
+        ",
+        todo_ids
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch checklists.",
+        )
+    })?
+    .into_iter()
+    .map(|record| {
+        (
+            record.todo_id,
+            ChecklistCounts {
+                item_count: record.item_count,
+                checked_item_count: record.checked_item_count,
+            },
+        )
+    })
+    .collect())
+}
+
+async fn fetch_checklist(pg_pool: &PgPool, todo_id: i64) -> APIResult<Vec<PublicChecklistItem>> {
+    Ok(sqlx::query_as!(
+        ChecklistItemRecord,
+        "
+            SELECT * FROM checklist_item
+            WHERE todo_id = $1
+            ORDER BY position, id
+        ",
+        todo_id
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch checklist.",
+        )
+    })?
+    .into_iter()
+    .map(PublicChecklistItem::from)
+    .collect())
+}
+
+pub async fn get_checklist(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+) -> APIResponse<Vec<PublicChecklistItem>> {
+    validate_account_has_todo(&pg_pool, account_id, todo_id).await?;
+
+    Ok(fetch_checklist(&pg_pool, todo_id).await?.into())
+}
+
+#[derive(Deserialize)]
+pub struct CreateChecklistItemRequest {
+    text: String,
+    #[serde(default)]
+    checked: bool,
+}
+
+/// Partial update of a checklist item. Missing fields are left unchanged.
+#[derive(Deserialize)]
+pub struct UpdateChecklistItemRequest {
+    text: Option<String>,
+    checked: Option<bool>,
+}
+
+/// The new order of the checklist of a todo, containing every item exactly
+/// once.
+#[derive(Deserialize)]
+pub struct ReorderChecklistRequest {
+    item_ids: Vec<i64>,
+}
+
+pub async fn post_checklist(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+    Json(req): Json<CreateChecklistItemRequest>,
+) -> APIResponse<PublicChecklistItem> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let item = sqlx::query_as!(
+        ChecklistItemRecord,
+        "
+            INSERT INTO checklist_item (todo_id, position, text, checked)
+            VALUES (
+                $1,
+                (SELECT COALESCE(MAX(position), -1) + 1 FROM checklist_item WHERE todo_id = $1),
+                $2,
+                $3
+            )
+            RETURNING *
+        ",
+        todo_id,
+        &req.text,
+        req.checked
+    )
+    .fetch_one(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create checklist item.",
+        )
+    })?;
+
+    touch_todo(&pg_pool, todo_id).await?;
+
+    Ok(PublicChecklistItem::from(item).into())
+}
+
+pub async fn put_checklist(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+    Json(req): Json<ReorderChecklistRequest>,
+) -> APIResponse<Vec<PublicChecklistItem>> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let current_item_ids = fetch_checklist(&pg_pool, todo_id)
+        .await?
+        .into_iter()
+        .map(|item| item.id)
+        .collect::<HashSet<_>>();
+    let requested_item_ids = req.item_ids.iter().copied().collect::<HashSet<_>>();
+
+    if requested_item_ids.len() != req.item_ids.len() || requested_item_ids != current_item_ids {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The order must contain every checklist item of the todo exactly once.",
+        ));
+    }
+
+    sqlx::query!(
+        "
+            UPDATE checklist_item
+            SET position = ordering.position - 1
+            FROM UNNEST($2::BIGINT[]) WITH ORDINALITY AS ordering(id, position)
+            WHERE checklist_item.id = ordering.id AND checklist_item.todo_id = $1
+        ",
+        todo_id,
+        &req.item_ids
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to reorder checklist.",
+        )
+    })?;
+
+    touch_todo(&pg_pool, todo_id).await?;
+
+    Ok(fetch_checklist(&pg_pool, todo_id).await?.into())
+}
+
+pub async fn post_checklist_item(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((todo_id, item_id)): Path<(i64, i64)>,
+    Json(req): Json<UpdateChecklistItemRequest>,
+) -> APIResponse<PublicChecklistItem> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let item = sqlx::query_as!(
+        ChecklistItemRecord,
+        "
+            UPDATE checklist_item
+            SET text = COALESCE($3, text),
+                checked = COALESCE($4, checked)
+            WHERE id = $1 AND todo_id = $2
+            RETURNING *
+        ",
+        item_id,
+        todo_id,
+        req.text,
+        req.checked
+    )
+    .fetch_optional(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update checklist item.",
+        )
+    })?
+    .ok_or_else(|| {
+        ErrorResponse::from(StatusCode::NOT_FOUND, "The checklist item does not exist.")
+    })?;
+
+    touch_todo(&pg_pool, todo_id).await?;
+
+    Ok(PublicChecklistItem::from(item).into())
+}
+
+pub async fn delete_checklist_item(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((todo_id, item_id)): Path<(i64, i64)>,
+) -> APIResponse<()> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let deleted = sqlx::query!(
+        "
+            DELETE FROM checklist_item
+            WHERE id = $1 AND todo_id = $2
+        ",
+        item_id,
+        todo_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete checklist item.",
+        )
+    })?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The checklist item does not exist.",
+        ));
+    }
+
+    touch_todo(&pg_pool, todo_id).await?;
+
+    Ok(().into())
+}
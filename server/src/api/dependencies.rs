@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+};
+use sqlx::postgres::PgPool;
+
+use crate::auth::AccountId;
+
+use super::{
+    todos::{touch_todo, validate_account_can_edit_todo, validate_account_has_todo},
+    APIResponse, APIResult, ErrorResponse,
+};
+
+/// The todos blocking a todo.
+#[derive(Default)]
+pub(super) struct Blockers {
+    pub blocked_by: Vec<i64>,
+    /// Whether any of the blocking todos is not completed yet.
+    pub blocked: bool,
+}
+
+/// Fetches the todos blocking each of the given todos.
+pub(super) async fn fetch_blockers_by_todo_id(
+    pg_pool: &PgPool,
+    todo_ids: &[i64],
+) -> APIResult<HashMap<i64, Blockers>> {
+    let mut blockers_by_todo_id = HashMap::<i64, Blockers>::new();
+
+    let records = sqlx::query!(
+        "
+            SELECT todo_dependency.todo_id, todo_dependency.blocked_by_todo_id, todo.completed_at
+            FROM todo_dependency
+            JOIN todo ON todo.id = todo_dependency.blocked_by_todo_id
+            WHERE todo_dependency.todo_id = ANY($1)
+            ORDER BY todo_dependency.created_at
+        ",
+        todo_ids
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch dependencies.",
+        )
+    })?;
+
+    for record in records {
+        let blockers = blockers_by_todo_id.entry(record.todo_id).or_default();
+        blockers.blocked_by.push(record.blocked_by_todo_id);
+        blockers.blocked |= record.completed_at.is_none();
+    }
+
+    Ok(blockers_by_todo_id)
+}
+
+/// Marks a todo as blocked by another todo. The link is rejected if the
+/// blocking todo is already, directly or indirectly, blocked by the todo.
+pub async fn post_todo_blocker(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((todo_id, blocked_by_todo_id)): Path<(i64, i64)>,
+) -> APIResponse<()> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+    validate_account_has_todo(&pg_pool, account_id, blocked_by_todo_id).await?;
+
+    if todo_id == blocked_by_todo_id {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "A todo cannot block itself.",
+        ));
+    }
+
+    let mut transaction = pg_pool.begin().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to add dependency.",
+        )
+    })?;
+
+    // Serialize dependency changes of the account, so that two concurrent
+    // changes cannot create a cycle together.
+    sqlx::query!("SELECT pg_advisory_xact_lock($1)", i64::from(account_id))
+        .execute(&mut transaction)
+        .await
+        .map_err(|_err| {
+            ErrorResponse::from(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to add dependency.",
+            )
+        })?;
+
+    let creates_cycle = sqlx::query!(
+        "
+            WITH RECURSIVE blocker(id) AS (
+                SELECT $2::BIGINT
+                UNION
+                SELECT todo_dependency.blocked_by_todo_id FROM todo_dependency
+                JOIN blocker ON todo_dependency.todo_id = blocker.id
+            )
+            SELECT $1 IN (SELECT id FROM blocker) AS \"creates_cycle!\"
+        ",
+        todo_id,
+        blocked_by_todo_id
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .map(|record| record.creates_cycle)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate dependency.",
+        )
+    })?;
+
+    if creates_cycle {
+        return Err(ErrorResponse::from(
+            StatusCode::CONFLICT,
+            "The dependency would create a cycle.",
+        ));
+    }
+
+    let inserted = sqlx::query!(
+        "
+            INSERT INTO todo_dependency (todo_id, blocked_by_todo_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+        ",
+        todo_id,
+        blocked_by_todo_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to add dependency.",
+        )
+    })?
+    .rows_affected();
+
+    transaction.commit().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to add dependency.",
+        )
+    })?;
+
+    if inserted > 0 {
+        touch_todo(&pg_pool, todo_id).await?;
+    }
+
+    Ok(().into())
+}
+
+pub async fn delete_todo_blocker(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((todo_id, blocked_by_todo_id)): Path<(i64, i64)>,
+) -> APIResponse<()> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let deleted = sqlx::query!(
+        "
+            DELETE FROM todo_dependency
+            WHERE todo_id = $1 AND blocked_by_todo_id = $2
+        ",
+        todo_id,
+        blocked_by_todo_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to remove dependency.",
+        )
+    })?
+    .rows_affected();
+
+    if deleted > 0 {
+        touch_todo(&pg_pool, todo_id).await?;
+    }
+
+    Ok(().into())
+}
@@ -0,0 +1,425 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+use crate::auth::AccountId;
+
+use super::{
+    todos::{validate_account_can_edit_project, validate_account_has_project},
+    APIResponse, APIResult, ErrorResponse,
+};
+
+#[derive(Serialize)]
+pub struct PublicIteration {
+    id: i64,
+    project_id: i64,
+    iteration_name: String,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    /// The number of todos committed to the iteration, including the todos
+    /// carried over to a later iteration.
+    committed_count: i64,
+    /// The number of committed todos completed while in the iteration.
+    completed_count: i64,
+    /// The number of committed todos carried over to a later iteration.
+    carried_over_count: i64,
+    /// The number of committed todos that are neither completed nor carried
+    /// over.
+    open_count: i64,
+    /// The number of committed todos added after the iteration started.
+    added_after_start_count: i64,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct IterationRecord {
+    id: i64,
+    project_id: i64,
+    iteration_name: String,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    committed_count: i64,
+    completed_count: i64,
+    carried_over_count: i64,
+    added_after_start_count: i64,
+}
+
+impl From<IterationRecord> for PublicIteration {
+    fn from(record: IterationRecord) -> Self {
+        PublicIteration {
+            id: record.id,
+            project_id: record.project_id,
+            iteration_name: record.iteration_name,
+            start_date: record.start_date,
+            end_date: record.end_date,
+            committed_count: record.committed_count,
+            completed_count: record.completed_count,
+            carried_over_count: record.carried_over_count,
+            open_count: record.committed_count - record.completed_count - record.carried_over_count,
+            added_after_start_count: record.added_after_start_count,
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+fn validate_iteration_dates(start_date: NaiveDate, end_date: NaiveDate) -> APIResult<()> {
+    if start_date > end_date {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "An iteration cannot end before it starts.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks that an iteration belongs to the project a todo is in or is moved
+/// to.
+pub(super) async fn validate_iteration(
+    pg_pool: &PgPool,
+    iteration_id: i64,
+    project_id: Option<i64>,
+) -> APIResult<()> {
+    let exists = sqlx::query!(
+        "
+            SELECT EXISTS (
+                SELECT 1 FROM iteration WHERE id = $1 AND project_id = $2
+            ) AS \"exists!\"
+        ",
+        iteration_id,
+        project_id
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map(|record| record.exists)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate iteration.",
+        )
+    })?;
+
+    if !exists {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The iteration does not belong to the project of the todo.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fetches the iterations of a project together with their summary, ordered
+/// by start date. Only the iteration with `iteration_id` is fetched if given.
+async fn fetch_iterations(
+    pg_pool: &PgPool,
+    project_id: i64,
+    iteration_id: Option<i64>,
+) -> APIResult<Vec<PublicIteration>> {
+    Ok(sqlx::query_as!(
+        IterationRecord,
+        "
+            SELECT
+                iteration.*,
+                COUNT(iteration_todo.todo_id) AS \"committed_count!\",
+                COUNT(*) FILTER (
+                    WHERE iteration_todo.carried_over_at IS NULL AND todo.completed_at IS NOT NULL
+                ) AS \"completed_count!\",
+                COUNT(iteration_todo.carried_over_at) AS \"carried_over_count!\",
+                COUNT(*) FILTER (
+                    WHERE iteration_todo.assigned_at >= iteration.start_date + 1
+                ) AS \"added_after_start_count!\"
+            FROM iteration
+            LEFT JOIN iteration_todo ON iteration_todo.iteration_id = iteration.id
+            LEFT JOIN todo ON todo.id = iteration_todo.todo_id
+            WHERE iteration.project_id = $1 AND ($2::BIGINT IS NULL OR iteration.id = $2)
+            GROUP BY iteration.id
+            ORDER BY iteration.start_date, iteration.id
+        ",
+        project_id,
+        iteration_id
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch iterations.",
+        )
+    })?
+    .into_iter()
+    .map(PublicIteration::from)
+    .collect())
+}
+
+async fn fetch_iteration(
+    pg_pool: &PgPool,
+    project_id: i64,
+    iteration_id: i64,
+) -> APIResult<PublicIteration> {
+    fetch_iterations(pg_pool, project_id, Some(iteration_id))
+        .await?
+        .pop()
+        .ok_or_else(|| ErrorResponse::from(StatusCode::NOT_FOUND, "The iteration does not exist."))
+}
+
+pub async fn get_iterations(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+) -> APIResponse<Vec<PublicIteration>> {
+    validate_account_has_project(&pg_pool, account_id, project_id).await?;
+
+    Ok(fetch_iterations(&pg_pool, project_id, None).await?.into())
+}
+
+pub async fn get_iteration(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, iteration_id)): Path<(i64, i64)>,
+) -> APIResponse<PublicIteration> {
+    validate_account_has_project(&pg_pool, account_id, project_id).await?;
+
+    Ok(fetch_iteration(&pg_pool, project_id, iteration_id)
+        .await?
+        .into())
+}
+
+#[derive(Deserialize)]
+pub struct CreateIterationRequest {
+    iteration_name: String,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+}
+
+/// Partial update of an iteration. Missing fields are left unchanged.
+#[derive(Deserialize)]
+pub struct UpdateIterationRequest {
+    iteration_name: Option<String>,
+    start_date: Option<NaiveDate>,
+    end_date: Option<NaiveDate>,
+}
+
+/// Moves the open todos of an iteration to `to_iteration_id`, or to the
+/// iteration that starts next if not given.
+#[derive(Deserialize)]
+pub struct CarryOverRequest {
+    to_iteration_id: Option<i64>,
+}
+
+pub async fn post_iterations(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Json(req): Json<CreateIterationRequest>,
+) -> APIResponse<PublicIteration> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+    validate_iteration_dates(req.start_date, req.end_date)?;
+
+    let iteration_id = sqlx::query!(
+        "
+            INSERT INTO iteration (project_id, iteration_name, start_date, end_date)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+        ",
+        project_id,
+        req.iteration_name.trim(),
+        req.start_date,
+        req.end_date
+    )
+    .fetch_one(&pg_pool)
+    .await
+    .map(|record| record.id)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create iteration.",
+        )
+    })?;
+
+    Ok(fetch_iteration(&pg_pool, project_id, iteration_id)
+        .await?
+        .into())
+}
+
+pub async fn post_iteration(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, iteration_id)): Path<(i64, i64)>,
+    Json(req): Json<UpdateIterationRequest>,
+) -> APIResponse<PublicIteration> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let current = fetch_iteration(&pg_pool, project_id, iteration_id).await?;
+    validate_iteration_dates(
+        req.start_date.unwrap_or(current.start_date),
+        req.end_date.unwrap_or(current.end_date),
+    )?;
+
+    sqlx::query!(
+        "
+            UPDATE iteration
+            SET iteration_name = COALESCE($3, iteration_name),
+                start_date = COALESCE($4, start_date),
+                end_date = COALESCE($5, end_date)
+            WHERE id = $1 AND project_id = $2
+        ",
+        iteration_id,
+        project_id,
+        req.iteration_name.as_deref().map(str::trim),
+        req.start_date,
+        req.end_date
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update iteration.",
+        )
+    })?;
+
+    Ok(fetch_iteration(&pg_pool, project_id, iteration_id)
+        .await?
+        .into())
+}
+
+/// Deletes an iteration. Its todos stay in the project without an iteration.
+pub async fn delete_iteration(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, iteration_id)): Path<(i64, i64)>,
+) -> APIResponse<()> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let deleted = sqlx::query!(
+        "
+            DELETE FROM iteration
+            WHERE id = $1 AND project_id = $2
+        ",
+        iteration_id,
+        project_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete iteration.",
+        )
+    })?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The iteration does not exist.",
+        ));
+    }
+
+    Ok(().into())
+}
+
+fn map_carry_over_error(_err: sqlx::Error) -> ErrorResponse {
+    ErrorResponse::from(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to carry over todos.",
+    )
+}
+
+/// Carries the open todos of an iteration over to another iteration. They
+/// stay committed to the iteration they were carried over from. Returns the
+/// iteration the todos were carried over to.
+pub async fn post_iteration_carry_over(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, iteration_id)): Path<(i64, i64)>,
+    Json(req): Json<CarryOverRequest>,
+) -> APIResponse<PublicIteration> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let from = fetch_iteration(&pg_pool, project_id, iteration_id).await?;
+
+    let to_iteration_id = match req.to_iteration_id {
+        Some(to_iteration_id) => {
+            validate_iteration(&pg_pool, to_iteration_id, Some(project_id)).await?;
+            to_iteration_id
+        }
+        None => sqlx::query!(
+            "
+                SELECT id FROM iteration
+                WHERE project_id = $1 AND start_date > $2
+                ORDER BY start_date, id
+                LIMIT 1
+            ",
+            project_id,
+            from.start_date
+        )
+        .fetch_optional(&pg_pool)
+        .await
+        .map_err(map_carry_over_error)?
+        .map(|record| record.id)
+        .ok_or_else(|| {
+            ErrorResponse::from(
+                StatusCode::CONFLICT,
+                "There is no later iteration to carry the todos over to.",
+            )
+        })?,
+    };
+
+    if to_iteration_id == iteration_id {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "Todos cannot be carried over to the same iteration.",
+        ));
+    }
+
+    let mut transaction = pg_pool.begin().await.map_err(map_carry_over_error)?;
+
+    // Mark the todos as carried over first, so that moving them keeps them
+    // committed to the iteration.
+    let carried_todo_ids = sqlx::query!(
+        "
+            UPDATE iteration_todo
+            SET carried_over_at = CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+            WHERE iteration_id = $1 AND todo_id IN (
+                SELECT id FROM todo WHERE iteration_id = $1 AND completed_at IS NULL
+            )
+            RETURNING todo_id
+        ",
+        iteration_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .map_err(map_carry_over_error)?
+    .into_iter()
+    .map(|record| record.todo_id)
+    .collect::<Vec<_>>();
+
+    sqlx::query!(
+        "
+            UPDATE todo
+            SET iteration_id = $2
+            WHERE id = ANY($1)
+        ",
+        &carried_todo_ids,
+        to_iteration_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_carry_over_error)?;
+
+    transaction.commit().await.map_err(map_carry_over_error)?;
+
+    Ok(fetch_iteration(&pg_pool, project_id, to_iteration_id)
+        .await?
+        .into())
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+use crate::auth::AccountId;
+
+use super::{
+    deserialize_optional_field,
+    todos::{validate_account_can_edit_project, validate_account_has_project},
+    APIResponse, APIResult, ErrorResponse,
+};
+
+#[derive(Serialize)]
+pub struct PublicMilestone {
+    id: i64,
+    project_id: i64,
+    milestone_name: String,
+    target_date: Option<NaiveDate>,
+    /// The number of todos assigned to the milestone.
+    todo_count: i64,
+    completed_todo_count: i64,
+    /// The percentage of assigned todos that are completed, or `None` if no
+    /// todos are assigned.
+    progress: Option<f64>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct MilestoneRecord {
+    id: i64,
+    project_id: i64,
+    milestone_name: String,
+    target_date: Option<NaiveDate>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    todo_count: i64,
+    completed_todo_count: i64,
+}
+
+impl From<MilestoneRecord> for PublicMilestone {
+    fn from(record: MilestoneRecord) -> Self {
+        PublicMilestone {
+            id: record.id,
+            project_id: record.project_id,
+            milestone_name: record.milestone_name,
+            target_date: record.target_date,
+            todo_count: record.todo_count,
+            completed_todo_count: record.completed_todo_count,
+            progress: (record.todo_count > 0)
+                .then(|| record.completed_todo_count as f64 / record.todo_count as f64 * 100.0),
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+/// Checks that a milestone belongs to the project a todo is in or is moved
+/// to.
+pub(super) async fn validate_milestone(
+    pg_pool: &PgPool,
+    milestone_id: i64,
+    project_id: Option<i64>,
+) -> APIResult<()> {
+    let exists = sqlx::query!(
+        "
+            SELECT EXISTS (
+                SELECT 1 FROM milestone WHERE id = $1 AND project_id = $2
+            ) AS \"exists!\"
+        ",
+        milestone_id,
+        project_id
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map(|record| record.exists)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate milestone.",
+        )
+    })?;
+
+    if !exists {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The milestone does not belong to the project of the todo.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fetches the milestones of the given projects together with the progress
+/// of their todos, ordered by target date. Only the milestone with
+/// `milestone_id` is fetched if given.
+async fn fetch_milestones(
+    pg_pool: &PgPool,
+    project_ids: &[i64],
+    milestone_id: Option<i64>,
+) -> APIResult<Vec<MilestoneRecord>> {
+    sqlx::query_as!(
+        MilestoneRecord,
+        "
+            SELECT
+                milestone.*,
+                COUNT(todo.id) AS \"todo_count!\",
+                COUNT(todo.completed_at) AS \"completed_todo_count!\"
+            FROM milestone
+            LEFT JOIN todo ON todo.milestone_id = milestone.id
+            WHERE milestone.project_id = ANY($1) AND ($2::BIGINT IS NULL OR milestone.id = $2)
+            GROUP BY milestone.id
+            ORDER BY milestone.target_date NULLS LAST, milestone.id
+        ",
+        project_ids,
+        milestone_id
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch milestones.",
+        )
+    })
+}
+
+/// Fetches the milestones of each of the given projects.
+pub(super) async fn fetch_milestones_by_project_id(
+    pg_pool: &PgPool,
+    project_ids: &[i64],
+) -> APIResult<HashMap<i64, Vec<PublicMilestone>>> {
+    let mut milestones_by_project_id = HashMap::<i64, Vec<PublicMilestone>>::new();
+
+    for record in fetch_milestones(pg_pool, project_ids, None).await? {
+        milestones_by_project_id
+            .entry(record.project_id)
+            .or_default()
+            .push(PublicMilestone::from(record));
+    }
+
+    Ok(milestones_by_project_id)
+}
+
+async fn fetch_milestone(
+    pg_pool: &PgPool,
+    project_id: i64,
+    milestone_id: i64,
+) -> APIResult<PublicMilestone> {
+    fetch_milestones(pg_pool, &[project_id], Some(milestone_id))
+        .await?
+        .pop()
+        .map(PublicMilestone::from)
+        .ok_or_else(|| ErrorResponse::from(StatusCode::NOT_FOUND, "The milestone does not exist."))
+}
+
+pub async fn get_milestones(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+) -> APIResponse<Vec<PublicMilestone>> {
+    validate_account_has_project(&pg_pool, account_id, project_id).await?;
+
+    Ok(fetch_milestones(&pg_pool, &[project_id], None)
+        .await?
+        .into_iter()
+        .map(PublicMilestone::from)
+        .collect::<Vec<_>>()
+        .into())
+}
+
+pub async fn get_milestone(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, milestone_id)): Path<(i64, i64)>,
+) -> APIResponse<PublicMilestone> {
+    validate_account_has_project(&pg_pool, account_id, project_id).await?;
+
+    Ok(fetch_milestone(&pg_pool, project_id, milestone_id)
+        .await?
+        .into())
+}
+
+#[derive(Deserialize)]
+pub struct CreateMilestoneRequest {
+    milestone_name: String,
+    target_date: Option<NaiveDate>,
+}
+
+/// Partial update of a milestone. Missing fields are left unchanged, while
+/// fields explicitly set to `null` are cleared.
+#[derive(Deserialize)]
+pub struct UpdateMilestoneRequest {
+    milestone_name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    target_date: Option<Option<NaiveDate>>,
+}
+
+pub async fn post_milestones(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Json(req): Json<CreateMilestoneRequest>,
+) -> APIResponse<PublicMilestone> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let milestone_id = sqlx::query!(
+        "
+            INSERT INTO milestone (project_id, milestone_name, target_date)
+            VALUES ($1, $2, $3)
+            RETURNING id
+        ",
+        project_id,
+        req.milestone_name.trim(),
+        req.target_date
+    )
+    .fetch_one(&pg_pool)
+    .await
+    .map(|record| record.id)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create milestone.",
+        )
+    })?;
+
+    Ok(fetch_milestone(&pg_pool, project_id, milestone_id)
+        .await?
+        .into())
+}
+
+pub async fn post_milestone(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, milestone_id)): Path<(i64, i64)>,
+    Json(req): Json<UpdateMilestoneRequest>,
+) -> APIResponse<PublicMilestone> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let updated = sqlx::query!(
+        "
+            UPDATE milestone
+            SET milestone_name = COALESCE($3, milestone_name),
+                target_date = CASE WHEN $4 THEN $5 ELSE target_date END
+            WHERE id = $1 AND project_id = $2
+        ",
+        milestone_id,
+        project_id,
+        req.milestone_name.as_deref().map(str::trim),
+        req.target_date.is_some(),
+        req.target_date.flatten()
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update milestone.",
+        )
+    })?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The milestone does not exist.",
+        ));
+    }
+
+    Ok(fetch_milestone(&pg_pool, project_id, milestone_id)
+        .await?
+        .into())
+}
+
+/// Deletes a milestone. Its todos stay in the project without a milestone.
+pub async fn delete_milestone(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, milestone_id)): Path<(i64, i64)>,
+) -> APIResponse<()> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let deleted = sqlx::query!(
+        "
+            DELETE FROM milestone
+            WHERE id = $1 AND project_id = $2
+        ",
+        milestone_id,
+        project_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete milestone.",
+        )
+    })?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The milestone does not exist.",
+        ));
+    }
+
+    Ok(().into())
+}
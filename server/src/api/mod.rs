@@ -1,28 +1,99 @@
+mod boards;
+mod checklists;
+mod dependencies;
 mod files;
+mod iterations;
+mod milestones;
+mod ordering;
 mod projects;
+mod recurrence;
+mod reminders;
+mod sections;
+mod snoozes;
+mod stats;
+mod tags;
+mod todo_templates;
 mod todos;
 mod users;
+mod workflow_states;
 
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
 };
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::etag::format_etag;
+
+use boards::*;
+use checklists::*;
+use dependencies::*;
 use files::*;
+use iterations::*;
+use milestones::*;
+use ordering::*;
 use projects::*;
+use reminders::*;
+use sections::*;
+use snoozes::*;
+use stats::*;
+use tags::*;
+use todo_templates::*;
 use todos::*;
 use users::*;
+use workflow_states::*;
 
 pub fn get_api_router() -> Router {
     Router::new()
-        .route("/user", get(get_user).post(post_user))
+        .route("/user", get(get_user).post(post_user).patch(post_user))
         .route("/users", post(post_users))
         .route("/login", post(post_login))
         .route("/todos", get(get_todos).post(post_todos))
-        .route("/todo/:todo_id", post(post_todo))
+        .route(
+            "/todo/:todo_id",
+            get(get_todo).post(post_todo).patch(post_todo),
+        )
+        .route("/todo/:todo_id/references", get(get_todo_references))
+        .route("/todo/:todo_id/move", post(post_todo_move))
+        .route("/todo/:todo_id/duplicate", post(post_todo_duplicate))
+        .route(
+            "/todo/:todo_id/snooze",
+            post(post_todo_snooze).delete(delete_todo_snooze),
+        )
+        .route("/todo/:todo_id/snoozes", get(get_todo_snoozes))
+        .route("/todo/:todo_id/transitions", get(get_todo_transitions))
+        .route(
+            "/todo/:todo_id/checklist",
+            get(get_checklist).post(post_checklist).put(put_checklist),
+        )
+        .route(
+            "/todo/:todo_id/checklist/:item_id",
+            post(post_checklist_item)
+                .patch(post_checklist_item)
+                .delete(delete_checklist_item),
+        )
+        .route("/reference/:reference", get(get_todo_by_reference))
+        .route(
+            "/todo_templates",
+            get(get_todo_templates).post(post_todo_templates),
+        )
+        .route(
+            "/todo_template/:template_id",
+            get(get_todo_template)
+                .post(post_todo_template)
+                .patch(post_todo_template)
+                .delete(delete_todo_template),
+        )
+        .route(
+            "/todo_template/:template_id/instantiate",
+            post(post_todo_template_instantiate),
+        )
+        .route(
+            "/todo/:todo_id/blocker/:blocked_by_todo_id",
+            post(post_todo_blocker).delete(delete_todo_blocker),
+        )
         .route(
             "/todo/:todo_id/files",
             get(get_todo_files).post(post_todo_files),
@@ -31,20 +102,133 @@ pub fn get_api_router() -> Router {
             "/todo/:todo_id/file/:file_id",
             get(get_todo_file).post(post_todo_file),
         )
+        .route(
+            "/todo/:todo_id/tag/:tag_id",
+            post(post_todo_tag).delete(delete_todo_tag),
+        )
+        .route(
+            "/todo/:todo_id/reminders",
+            get(get_reminders).post(post_reminders),
+        )
+        .route(
+            "/todo/:todo_id/reminder/:reminder_id",
+            post(post_reminder)
+                .patch(post_reminder)
+                .delete(delete_reminder),
+        )
+        .route("/notifications", get(get_notifications))
+        .route(
+            "/notification/:notification_id/read",
+            post(post_notification_read),
+        )
         .route("/files", get(get_files))
+        .route("/tags", get(get_tags).post(post_tags))
+        .route(
+            "/tag/:tag_id",
+            get(get_tag)
+                .post(post_tag)
+                .patch(post_tag)
+                .delete(delete_tag),
+        )
+        .route("/tag/:tag_id/merge", post(post_tag_merge))
         .route("/projects", get(get_projects).post(post_projects))
-        .route("/project/:project_id", post(post_project))
+        .route(
+            "/project/:project_id",
+            get(get_project)
+                .post(post_project)
+                .patch(post_project)
+                .delete(delete_project),
+        )
+        .route(
+            "/project/:project_id/sections",
+            get(get_sections).post(post_sections).put(put_sections),
+        )
+        .route(
+            "/project/:project_id/section/:section_id",
+            post(post_section)
+                .patch(post_section)
+                .delete(delete_section),
+        )
+        .route(
+            "/project/:project_id/milestones",
+            get(get_milestones).post(post_milestones),
+        )
+        .route(
+            "/project/:project_id/milestone/:milestone_id",
+            get(get_milestone)
+                .post(post_milestone)
+                .patch(post_milestone)
+                .delete(delete_milestone),
+        )
+        .route(
+            "/project/:project_id/iterations",
+            get(get_iterations).post(post_iterations),
+        )
+        .route(
+            "/project/:project_id/iteration/:iteration_id",
+            get(get_iteration)
+                .post(post_iteration)
+                .patch(post_iteration)
+                .delete(delete_iteration),
+        )
+        .route(
+            "/project/:project_id/iteration/:iteration_id/carry_over",
+            post(post_iteration_carry_over),
+        )
+        .route(
+            "/project/:project_id/duplicate",
+            post(post_project_duplicate),
+        )
+        .route("/project/:project_id/stats", get(get_project_stats))
+        .route("/project/:project_id/board", get(get_board))
+        .route("/project/:project_id/board/move", post(post_board_move))
+        .route(
+            "/project/:project_id/states",
+            get(get_workflow_states)
+                .post(post_workflow_states)
+                .put(put_workflow_states),
+        )
+        .route(
+            "/project/:project_id/state/:state_id",
+            post(post_workflow_state)
+                .patch(post_workflow_state)
+                .delete(delete_workflow_state),
+        )
+}
+
+/// Deserializes a field of a partial update request, distinguishing a missing
+/// field (`None`) from an explicit `null` (`Some(None)`).
+///
+/// Must be used together with `#[serde(default)]` so that missing fields are
+/// deserialized as `None`.
+fn deserialize_optional_field<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Whether a query failed because it violated a unique constraint.
+fn is_unique_violation(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(err) => err.code().as_deref() == Some("23505"),
+        _ => false,
+    }
 }
 
 #[derive(Serialize)]
 struct ErrorResponseBody {
     success: bool,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
 }
 
 pub struct ErrorResponse {
     status_code: StatusCode,
     body: ErrorResponseBody,
+    etag: Option<String>,
 }
 
 impl ErrorResponse {
@@ -54,14 +238,36 @@ impl ErrorResponse {
             body: ErrorResponseBody {
                 success: false,
                 message: error_message.to_owned(),
+                data: None,
             },
+            etag: None,
         }
     }
+
+    /// Responds with 412 Precondition Failed, attaching the current state of
+    /// the resource and its entity tag so that the client can resolve the
+    /// conflict.
+    fn precondition_failed<T>(current: T, version: i32) -> Self
+    where
+        T: Serialize,
+    {
+        let mut response = ErrorResponse::from(
+            StatusCode::PRECONDITION_FAILED,
+            "The resource has been modified.",
+        );
+        response.body.data = serde_json::to_value(current).ok();
+        response.etag = Some(format_etag(version));
+        response
+    }
 }
 
 impl IntoResponse for ErrorResponse {
     fn into_response(self) -> axum::response::Response {
-        (self.status_code, Json(self.body)).into_response()
+        let mut response = (self.status_code, Json(self.body)).into_response();
+        if let Some(etag) = self.etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+            response.headers_mut().insert(header::ETAG, etag);
+        }
+        response
     }
 }
 
@@ -69,6 +275,15 @@ impl IntoResponse for ErrorResponse {
 pub struct SuccessResponse<T> {
     success: bool,
     data: T,
+    #[serde(skip)]
+    etag: Option<String>,
+}
+
+impl<T> SuccessResponse<T> {
+    fn with_etag(mut self, version: i32) -> Self {
+        self.etag = Some(format_etag(version));
+        self
+    }
 }
 
 impl<T> From<T> for SuccessResponse<T> {
@@ -76,6 +291,7 @@ impl<T> From<T> for SuccessResponse<T> {
         SuccessResponse {
             success: true,
             data,
+            etag: None,
         }
     }
 }
@@ -85,7 +301,15 @@ where
     T: Serialize,
 {
     fn into_response(self) -> axum::response::Response {
-        (StatusCode::OK, Json(self)).into_response()
+        let etag = self
+            .etag
+            .as_ref()
+            .and_then(|etag| HeaderValue::from_str(etag).ok());
+        let mut response = (StatusCode::OK, Json(self)).into_response();
+        if let Some(etag) = etag {
+            response.headers_mut().insert(header::ETAG, etag);
+        }
+        response
     }
 }
 
@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+
+use crate::auth::AccountId;
+
+use super::{
+    todos::{fetch_todo, todo_response, validate_account_can_edit_todo, PublicTodo},
+    APIResponse, APIResult, ErrorResponse,
+};
+
+/// The distance between the ranks of neighbouring todos after they are
+/// appended to a list or the list is rebalanced.
+pub(super) const RANK_GAP: i64 = 1 << 16;
+
+/// Where to move a todo within its list. The todo is placed directly after
+/// `after_todo_id` if given, otherwise directly before `before_todo_id` if
+/// given, otherwise at the end of the list.
+#[derive(Deserialize)]
+pub struct MoveTodoRequest {
+    pub(super) after_todo_id: Option<i64>,
+    pub(super) before_todo_id: Option<i64>,
+}
+
+fn map_move_error(_err: sqlx::Error) -> ErrorResponse {
+    ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to move todo.")
+}
+
+/// Moves a todo within its list, which is its project or the inbox for todos
+/// without a project. Only the moved todo is rewritten, unless there is no
+/// gap left between its new neighbours, in which case the list is rebalanced.
+pub(super) async fn place_todo(
+    transaction: &mut Transaction<'_, Postgres>,
+    account_id: i32,
+    todo_id: i64,
+    req: &MoveTodoRequest,
+) -> APIResult<()> {
+    // Serialize reordering of the account, so that concurrent moves do not
+    // compute ranks from stale neighbours.
+    sqlx::query!("SELECT pg_advisory_xact_lock($1)", i64::from(account_id))
+        .execute(&mut *transaction)
+        .await
+        .map_err(map_move_error)?;
+
+    let mut list = sqlx::query!(
+        "
+            SELECT id, sort_rank FROM todo
+            WHERE account_id = $1
+                AND project_id IS NOT DISTINCT FROM (SELECT project_id FROM todo WHERE id = $2)
+                AND id <> $2
+            ORDER BY sort_rank, id
+        ",
+        account_id,
+        todo_id
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .map_err(map_move_error)?
+    .into_iter()
+    .map(|record| (record.id, record.sort_rank))
+    .collect::<Vec<_>>();
+
+    let find_neighbour = |neighbour_id: i64| {
+        list.iter()
+            .position(|(id, _rank)| *id == neighbour_id)
+            .ok_or_else(|| {
+                ErrorResponse::from(
+                    StatusCode::BAD_REQUEST,
+                    "The todo can only be placed next to other todos in the same list.",
+                )
+            })
+    };
+    let index = match (req.after_todo_id, req.before_todo_id) {
+        (Some(after_todo_id), _) => find_neighbour(after_todo_id)? + 1,
+        (None, Some(before_todo_id)) => find_neighbour(before_todo_id)?,
+        (None, None) => list.len(),
+    };
+
+    let lower = index.checked_sub(1).map(|index| list[index].1);
+    let upper = list.get(index).map(|(_id, rank)| *rank);
+    let rank = match (lower, upper) {
+        (Some(lower), Some(upper)) if upper - lower >= 2 => Some(lower + (upper - lower) / 2),
+        (Some(_lower), Some(_upper)) => None,
+        (Some(lower), None) => Some(lower + RANK_GAP),
+        (None, Some(upper)) => Some(upper - RANK_GAP),
+        (None, None) => Some(RANK_GAP),
+    };
+
+    match rank {
+        Some(rank) => {
+            sqlx::query!(
+                "UPDATE todo SET sort_rank = $2 WHERE id = $1",
+                todo_id,
+                rank
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_move_error)?;
+        }
+        None => {
+            list.insert(index, (todo_id, 0));
+            let ids = list.iter().map(|(id, _rank)| *id).collect::<Vec<_>>();
+            let ranks = (1..=list.len() as i64)
+                .map(|position| position * RANK_GAP)
+                .collect::<Vec<_>>();
+
+            sqlx::query!(
+                "
+                    UPDATE todo
+                    SET sort_rank = rebalanced.sort_rank
+                    FROM UNNEST($1::BIGINT[], $2::BIGINT[]) AS rebalanced(id, sort_rank)
+                    WHERE todo.id = rebalanced.id AND todo.sort_rank <> rebalanced.sort_rank
+                ",
+                &ids,
+                &ranks
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_move_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn post_todo_move(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+    Json(req): Json<MoveTodoRequest>,
+) -> APIResponse<PublicTodo> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let mut transaction = pg_pool.begin().await.map_err(map_move_error)?;
+    place_todo(&mut transaction, account_id, todo_id, &req).await?;
+    transaction.commit().await.map_err(map_move_error)?;
+
+    let record = fetch_todo(&pg_pool, account_id, todo_id).await?;
+
+    todo_response(&pg_pool, record).await
+}
@@ -1,32 +1,136 @@
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     http::StatusCode,
     Json,
 };
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
 
-use crate::auth::AccountId;
+use crate::{auth::AccountId, etag::IfMatch};
 
-use super::{APIResponse, ErrorResponse};
+use super::{
+    deserialize_optional_field, is_unique_violation,
+    milestones::{fetch_milestones_by_project_id, PublicMilestone},
+    ordering::RANK_GAP,
+    sections::{fetch_sections_by_project_id, PublicSection},
+    APIResponse, APIResult, ErrorResponse, SuccessResponse,
+};
 
 #[derive(Serialize)]
 pub struct PublicProject {
     id: i64,
     shortcode: String,
     project_name: String,
+    /// The date the project starts, which the dates of copies of the project
+    /// are shifted relative to.
+    start_date: Option<NaiveDate>,
+    /// Whether the project is a template. Templates and their todos are hidden
+    /// from listings by default.
+    is_template: bool,
+    /// The sections of the project, in their order.
+    sections: Vec<PublicSection>,
+    /// The milestones of the project, by target date.
+    milestones: Vec<PublicMilestone>,
+    /// When the project was archived. Archived projects and their todos are
+    /// hidden from listings by default and cannot be modified.
+    archived_at: Option<DateTime<Utc>>,
+    version: i32,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct ProjectRecord {
+    id: i64,
+    #[allow(dead_code)]
+    account_id: Option<i32>,
+    shortcode: String,
+    project_name: String,
+    version: i32,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    archived_at: Option<NaiveDateTime>,
+    start_date: Option<NaiveDate>,
+    is_template: bool,
+}
+
+impl From<ProjectRecord> for PublicProject {
+    fn from(record: ProjectRecord) -> Self {
+        PublicProject {
+            id: record.id,
+            shortcode: record.shortcode,
+            project_name: record.project_name,
+            start_date: record.start_date,
+            is_template: record.is_template,
+            sections: Vec::new(),
+            milestones: Vec::new(),
+            archived_at: record
+                .archived_at
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+            version: record.version,
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+/// Converts project records into their public representation, loading their
+/// sections and milestones.
+async fn to_public_projects(
+    pg_pool: &PgPool,
+    records: Vec<ProjectRecord>,
+) -> APIResult<Vec<PublicProject>> {
+    let project_ids = records.iter().map(|record| record.id).collect::<Vec<_>>();
+    let mut sections_by_project_id = fetch_sections_by_project_id(pg_pool, &project_ids).await?;
+    let mut milestones_by_project_id =
+        fetch_milestones_by_project_id(pg_pool, &project_ids).await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| PublicProject {
+            sections: sections_by_project_id
+                .remove(&record.id)
+                .unwrap_or_default(),
+            milestones: milestones_by_project_id
+                .remove(&record.id)
+                .unwrap_or_default(),
+            ..PublicProject::from(record)
+        })
+        .collect())
+}
+
+async fn to_public_project(pg_pool: &PgPool, record: ProjectRecord) -> APIResult<PublicProject> {
+    Ok(to_public_projects(pg_pool, vec![record])
+        .await?
+        .pop()
+        .expect("one project is converted from one record"))
+}
+
+#[derive(Deserialize)]
+pub struct GetProjectsQuery {
+    /// Also return archived projects.
+    #[serde(default)]
+    include_archived: bool,
+    /// Return templates instead of projects.
+    #[serde(default)]
+    templates: bool,
 }
 
 pub async fn get_projects(
     AccountId(account_id): AccountId,
     Extension(pg_pool): Extension<PgPool>,
+    Query(query): Query<GetProjectsQuery>,
 ) -> APIResponse<Vec<PublicProject>> {
-    Ok(sqlx::query!(
+    let records = sqlx::query_as!(
+        ProjectRecord,
         "
             SELECT * FROM project
-            WHERE account_id = $1
+            WHERE account_id = $1 AND ($2 OR archived_at IS NULL) AND is_template = $3
         ",
-        account_id
+        account_id,
+        query.include_archived,
+        query.templates
     )
     .fetch_all(&pg_pool)
     .await
@@ -35,55 +139,624 @@ pub async fn get_projects(
             StatusCode::INTERNAL_SERVER_ERROR,
             "Failed to fetch projects.",
         )
-    })?
-    .into_iter()
-    .map(|project| PublicProject {
-        id: project.id,
-        shortcode: project.shortcode,
-        project_name: project.project_name,
-    })
-    .collect::<Vec<_>>()
-    .into())
+    })?;
+
+    Ok(to_public_projects(&pg_pool, records).await?.into())
+}
+
+/// The maximum length of a project shortcode.
+const MAX_SHORTCODE_LENGTH: usize = 10;
+
+/// Validates that a shortcode can be used in references such as `ABC-12`. A
+/// shortcode consists of 2 to 10 uppercase letters and digits, starting with a
+/// letter.
+fn validate_shortcode(shortcode: &str) -> APIResult<()> {
+    let valid = (2..=MAX_SHORTCODE_LENGTH).contains(&shortcode.len())
+        && shortcode.starts_with(|c: char| c.is_ascii_uppercase())
+        && shortcode
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+
+    if !valid {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "A shortcode must consist of 2 to 10 uppercase letters and digits, starting with a letter.",
+        ));
+    }
+
+    Ok(())
+}
+
+fn map_project_write_error(err: sqlx::Error, message: &'static str) -> ErrorResponse {
+    // Raised by the database when the shortcode is a previous shortcode of
+    // another project, whose old references must keep resolving to it.
+    let aliased = matches!(
+        &err,
+        sqlx::Error::Database(err) if err.constraint() == Some("project_shortcode_alias_pkey")
+    );
+
+    if aliased {
+        ErrorResponse::from(
+            StatusCode::CONFLICT,
+            "The shortcode was previously used by another project.",
+        )
+    } else if is_unique_violation(&err) {
+        ErrorResponse::from(
+            StatusCode::CONFLICT,
+            "A project with the same shortcode already exists.",
+        )
+    } else {
+        ErrorResponse::from(StatusCode::BAD_REQUEST, message)
+    }
 }
 
 #[derive(Deserialize)]
-pub struct CreateOrUpdateProjectRequest {
+pub struct CreateProjectRequest {
     shortcode: String,
     project_name: String,
+    start_date: Option<NaiveDate>,
+}
+
+/// Partial update of a project. Missing fields are left unchanged. An
+/// archived project can only be unarchived, optionally together with other
+/// changes.
+#[derive(Deserialize)]
+pub struct UpdateProjectRequest {
+    shortcode: Option<String>,
+    project_name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    start_date: Option<Option<NaiveDate>>,
+    archived: Option<bool>,
+}
+
+/// Copies a project into a new project, or into a template when `template` is
+/// set. Copying a template creates a project from it.
+///
+/// The todos are copied with their subtasks, checklists, tags, and
+/// dependencies, reopened and numbered from 1. When `start_date` is given, the
+/// dates of the todos and milestones are shifted by the days between the start
+/// date of the copied project and `start_date`.
+#[derive(Deserialize)]
+pub struct DuplicateProjectRequest {
+    shortcode: String,
+    project_name: String,
+    #[serde(default)]
+    template: bool,
+    start_date: Option<NaiveDate>,
+}
+
+/// What happens to the todos of a deleted project.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletedProjectTodos {
+    /// Delete the todos together with the project.
+    Cascade,
+    /// Keep the todos without a project.
+    Detach,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteProjectQuery {
+    /// Required if the project has todos.
+    todos: Option<DeletedProjectTodos>,
 }
 
 pub async fn post_projects(
     AccountId(account_id): AccountId,
     Extension(pg_pool): Extension<PgPool>,
-    Json(req): Json<CreateOrUpdateProjectRequest>,
+    Json(req): Json<CreateProjectRequest>,
 ) -> APIResponse<PublicProject> {
-    let project = sqlx::query!(
+    validate_shortcode(&req.shortcode)?;
+    let project = sqlx::query_as!(
+        ProjectRecord,
         "
-            INSERT INTO project (account_id, shortcode, project_name)
-            VALUES ($1, $2, $3)
+            INSERT INTO project (account_id, shortcode, project_name, start_date)
+            VALUES ($1, $2, $3, $4)
             RETURNING *
         ",
         account_id,
         &req.shortcode,
-        &req.project_name
+        &req.project_name,
+        req.start_date
     )
     .fetch_one(&pg_pool)
     .await
-    .map_err(|_err| ErrorResponse::from(StatusCode::BAD_REQUEST, "Failed to create project."))?;
+    .map_err(|err| map_project_write_error(err, "Failed to create project."))?;
 
-    Ok(PublicProject {
-        id: project.id,
-        shortcode: project.shortcode,
-        project_name: project.project_name,
-    }
-    .into())
+    let version = project.version;
+
+    Ok(SuccessResponse::from(to_public_project(&pg_pool, project).await?).with_etag(version))
+}
+
+pub async fn get_project(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+) -> APIResponse<PublicProject> {
+    let project = fetch_project(&pg_pool, account_id, project_id).await?;
+    let version = project.version;
+
+    Ok(SuccessResponse::from(to_public_project(&pg_pool, project).await?).with_etag(version))
+}
+
+async fn fetch_project(
+    pg_pool: &PgPool,
+    account_id: i32,
+    project_id: i64,
+) -> APIResult<ProjectRecord> {
+    sqlx::query_as!(
+        ProjectRecord,
+        "
+            SELECT * FROM project
+            WHERE id = $1 AND account_id = $2
+        ",
+        project_id,
+        account_id
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch project.",
+        )
+    })?
+    .ok_or_else(|| ErrorResponse::from(StatusCode::NOT_FOUND, "Project does not exist."))
 }
 
 pub async fn post_project(
-    AccountId(_account_id): AccountId,
-    Extension(_pg_pool): Extension<PgPool>,
-    Path(_project_id): Path<i64>,
-    Json(_req): Json<CreateOrUpdateProjectRequest>,
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    IfMatch(expected_version): IfMatch,
+    Json(req): Json<UpdateProjectRequest>,
+) -> APIResponse<PublicProject> {
+    let current = fetch_project(&pg_pool, account_id, project_id).await?;
+
+    if current.archived_at.is_some() && req.archived != Some(false) {
+        return Err(ErrorResponse::from(
+            StatusCode::CONFLICT,
+            "The project is archived.",
+        ));
+    }
+    if let Some(shortcode) = &req.shortcode {
+        validate_shortcode(shortcode)?;
+    }
+
+    let project = sqlx::query_as!(
+        ProjectRecord,
+        "
+            UPDATE project
+            SET shortcode = COALESCE($3, shortcode),
+                project_name = COALESCE($4, project_name),
+                start_date = CASE WHEN $7 THEN $8 ELSE start_date END,
+                archived_at = CASE
+                    WHEN $6::BOOLEAN IS NULL THEN archived_at
+                    WHEN $6 THEN COALESCE(archived_at, CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+                    ELSE NULL
+                END
+            WHERE id = $1 AND account_id = $2 AND ($5::INT IS NULL OR version = $5)
+            RETURNING *
+        ",
+        project_id,
+        account_id,
+        req.shortcode,
+        req.project_name,
+        expected_version,
+        req.archived,
+        req.start_date.is_some(),
+        req.start_date.flatten()
+    )
+    .fetch_optional(&pg_pool)
+    .await
+    .map_err(|err| map_project_write_error(err, "Failed to update project."))?;
+
+    let project = match project {
+        Some(project) => project,
+        None => {
+            let current = fetch_project(&pg_pool, account_id, project_id).await?;
+            let version = current.version;
+            return Err(ErrorResponse::precondition_failed(
+                to_public_project(&pg_pool, current).await?,
+                version,
+            ));
+        }
+    };
+    let version = project.version;
+
+    Ok(SuccessResponse::from(to_public_project(&pg_pool, project).await?).with_etag(version))
+}
+
+fn map_duplicate_project_error(_err: sqlx::Error) -> ErrorResponse {
+    ErrorResponse::from(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to duplicate project.",
+    )
+}
+
+/// The ids of copied rows, each together with the id of its copy.
+type CopiedIds = Vec<(i64, i64)>;
+
+/// Copies the sections and milestones of a project.
+async fn copy_project_groupings(
+    transaction: &mut Transaction<'_, Postgres>,
+    source_project_id: i64,
+    project_id: i64,
+    shift_days: i32,
+) -> APIResult<(CopiedIds, CopiedIds)> {
+    let sections = sqlx::query!(
+        "
+            WITH mapping AS (
+                SELECT id AS source_id, nextval('project_section_id_seq') AS copy_id
+                FROM project_section
+                WHERE project_id = $1
+            ), copied AS (
+                INSERT INTO project_section (id, project_id, section_name, position)
+                SELECT mapping.copy_id, $2, project_section.section_name, project_section.position
+                FROM mapping
+                JOIN project_section ON project_section.id = mapping.source_id
+            )
+            SELECT source_id, copy_id AS \"copy_id!\" FROM mapping
+        ",
+        source_project_id,
+        project_id
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .map_err(map_duplicate_project_error)?
+    .into_iter()
+    .map(|record| (record.source_id, record.copy_id))
+    .collect();
+
+    let milestones = sqlx::query!(
+        "
+            WITH mapping AS (
+                SELECT id AS source_id, nextval('milestone_id_seq') AS copy_id
+                FROM milestone
+                WHERE project_id = $1
+            ), copied AS (
+                INSERT INTO milestone (id, project_id, milestone_name, target_date)
+                SELECT mapping.copy_id, $2, milestone.milestone_name, milestone.target_date + $3::INT
+                FROM mapping
+                JOIN milestone ON milestone.id = mapping.source_id
+            )
+            SELECT source_id, copy_id AS \"copy_id!\" FROM mapping
+        ",
+        source_project_id,
+        project_id,
+        shift_days
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .map_err(map_duplicate_project_error)?
+    .into_iter()
+    .map(|record| (record.source_id, record.copy_id))
+    .collect();
+
+    Ok((sections, milestones))
+}
+
+/// Copies the todos of a project together with their checklists, tags, and
+/// dependencies on each other. The copies are numbered from 1 in the order of
+/// the original numbers and are reopened in their workflow state, or in the
+/// first open state if the original was closed.
+async fn copy_project_todos(
+    transaction: &mut Transaction<'_, Postgres>,
+    source_project_id: i64,
+    project_id: i64,
+    shift_days: i32,
+    (sections, milestones): (CopiedIds, CopiedIds),
+) -> APIResult<()> {
+    let (section_ids, section_copy_ids): (Vec<_>, Vec<_>) = sections.into_iter().unzip();
+    let (milestone_ids, milestone_copy_ids): (Vec<_>, Vec<_>) = milestones.into_iter().unzip();
+
+    let (todo_ids, todo_copy_ids): (Vec<i64>, Vec<i64>) = sqlx::query!(
+        "
+            WITH mapping AS (
+                SELECT
+                    id AS source_id,
+                    nextval('todo_id_seq') AS copy_id,
+                    ROW_NUMBER() OVER (ORDER BY project_todo_number NULLS LAST, id) AS number
+                FROM todo
+                WHERE project_id = $1
+            ), section_mapping AS (
+                SELECT * FROM UNNEST($4::BIGINT[], $5::BIGINT[]) AS section_mapping(source_id, copy_id)
+            ), milestone_mapping AS (
+                SELECT * FROM UNNEST($6::BIGINT[], $7::BIGINT[]) AS milestone_mapping(source_id, copy_id)
+            ), copied AS (
+                INSERT INTO todo (
+                    id, account_id, title, memo, deadline, deadline_date, start_date, scheduled_date,
+                    project_id, project_todo_number, parent_todo_id, priority, sort_rank,
+                    recurrence, recurs_from_completion, workflow_state_id, section_id, milestone_id
+                )
+                SELECT
+                    mapping.copy_id,
+                    todo.account_id,
+                    todo.title,
+                    todo.memo,
+                    (
+                        (todo.deadline AT TIME ZONE 'UTC' AT TIME ZONE account.time_zone) + $3::INT * INTERVAL '1 day'
+                    ) AT TIME ZONE account.time_zone AT TIME ZONE 'UTC',
+                    todo.deadline_date + $3,
+                    todo.start_date + $3,
+                    todo.scheduled_date + $3,
+                    $2,
+                    mapping.number,
+                    parent_mapping.copy_id,
+                    todo.priority,
+                    todo.sort_rank,
+                    todo.recurrence,
+                    todo.recurs_from_completion,
+                    (
+                        SELECT copy.id FROM workflow_state copy
+                        JOIN workflow_state source ON source.state_name = copy.state_name
+                        WHERE copy.project_id = $2
+                            AND source.id = todo.workflow_state_id
+                            AND source.category <> 'closed'
+                    ),
+                    section_mapping.copy_id,
+                    milestone_mapping.copy_id
+                FROM mapping
+                JOIN todo ON todo.id = mapping.source_id
+                JOIN account ON account.id = todo.account_id
+                LEFT JOIN mapping parent_mapping ON parent_mapping.source_id = todo.parent_todo_id
+                LEFT JOIN section_mapping ON section_mapping.source_id = todo.section_id
+                LEFT JOIN milestone_mapping ON milestone_mapping.source_id = todo.milestone_id
+            )
+            SELECT source_id, copy_id AS \"copy_id!\" FROM mapping
+        ",
+        source_project_id,
+        project_id,
+        shift_days,
+        &section_ids,
+        &section_copy_ids,
+        &milestone_ids,
+        &milestone_copy_ids
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .map_err(map_duplicate_project_error)?
+    .into_iter()
+    .map(|record| (record.source_id, record.copy_id))
+    .unzip();
+
+    sqlx::query!(
+        "
+            INSERT INTO project_todo_counter (project_id, last_todo_number)
+            VALUES ($1, $2)
+        ",
+        project_id,
+        todo_ids.len() as i32
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(map_duplicate_project_error)?;
+
+    sqlx::query!(
+        "
+            INSERT INTO todo_tag (todo_id, tag_id)
+            SELECT mapping.copy_id, todo_tag.tag_id
+            FROM UNNEST($1::BIGINT[], $2::BIGINT[]) AS mapping(source_id, copy_id)
+            JOIN todo_tag ON todo_tag.todo_id = mapping.source_id
+        ",
+        &todo_ids,
+        &todo_copy_ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(map_duplicate_project_error)?;
+
+    sqlx::query!(
+        "
+            INSERT INTO checklist_item (todo_id, position, text)
+            SELECT mapping.copy_id, checklist_item.position, checklist_item.text
+            FROM UNNEST($1::BIGINT[], $2::BIGINT[]) AS mapping(source_id, copy_id)
+            JOIN checklist_item ON checklist_item.todo_id = mapping.source_id
+        ",
+        &todo_ids,
+        &todo_copy_ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(map_duplicate_project_error)?;
+
+    sqlx::query!(
+        "
+            WITH mapping AS (
+                SELECT * FROM UNNEST($1::BIGINT[], $2::BIGINT[]) AS mapping(source_id, copy_id)
+            )
+            INSERT INTO todo_dependency (todo_id, blocked_by_todo_id)
+            SELECT todo_mapping.copy_id, blocker_mapping.copy_id
+            FROM todo_dependency
+            JOIN mapping todo_mapping ON todo_mapping.source_id = todo_dependency.todo_id
+            JOIN mapping blocker_mapping
+                ON blocker_mapping.source_id = todo_dependency.blocked_by_todo_id
+        ",
+        &todo_ids,
+        &todo_copy_ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(map_duplicate_project_error)?;
+
+    Ok(())
+}
+
+pub async fn post_project_duplicate(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Json(req): Json<DuplicateProjectRequest>,
+) -> APIResponse<PublicProject> {
+    let source = fetch_project(&pg_pool, account_id, project_id).await?;
+
+    validate_shortcode(&req.shortcode)?;
+    let shift_days = match (source.start_date, req.start_date) {
+        (Some(source_start_date), Some(start_date)) => {
+            (start_date - source_start_date).num_days() as i32
+        }
+        (None, Some(_)) => {
+            return Err(ErrorResponse::from(
+                StatusCode::BAD_REQUEST,
+                "The project has no start date to shift its dates from.",
+            ))
+        }
+        (_, None) => 0,
+    };
+
+    let mut transaction = pg_pool.begin().await.map_err(map_duplicate_project_error)?;
+
+    let project = sqlx::query_as!(
+        ProjectRecord,
+        "
+            INSERT INTO project (account_id, shortcode, project_name, start_date, is_template)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+        ",
+        account_id,
+        &req.shortcode,
+        &req.project_name,
+        req.start_date.or(source.start_date),
+        req.template
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .map_err(|err| map_project_write_error(err, "Failed to duplicate project."))?;
+
+    // The copy keeps the workflow states of the project instead of the
+    // default states.
+    sqlx::query!(
+        "DELETE FROM workflow_state WHERE project_id = $1",
+        project.id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_duplicate_project_error)?;
+
+    sqlx::query!(
+        "
+            INSERT INTO workflow_state (project_id, state_name, category, position)
+            SELECT $2, state_name, category, position FROM workflow_state
+            WHERE project_id = $1
+        ",
+        project_id,
+        project.id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_duplicate_project_error)?;
+
+    let groupings =
+        copy_project_groupings(&mut transaction, project_id, project.id, shift_days).await?;
+    copy_project_todos(
+        &mut transaction,
+        project_id,
+        project.id,
+        shift_days,
+        groupings,
+    )
+    .await?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(map_duplicate_project_error)?;
+
+    let version = project.version;
+
+    Ok(SuccessResponse::from(to_public_project(&pg_pool, project).await?).with_etag(version))
+}
+
+fn map_delete_project_error(_err: sqlx::Error) -> ErrorResponse {
+    ErrorResponse::from(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to delete project.",
+    )
+}
+
+/// Deletes a project together with its workflow states, sections, milestones,
+/// and iterations. Its todos are either deleted, including their subtasks, or
+/// kept at the end of the inbox.
+pub async fn delete_project(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Query(query): Query<DeleteProjectQuery>,
 ) -> APIResponse<()> {
-    Err(ErrorResponse::from(StatusCode::NOT_FOUND, "Unimplemented"))
+    fetch_project(&pg_pool, account_id, project_id).await?;
+
+    let mut transaction = pg_pool.begin().await.map_err(map_delete_project_error)?;
+
+    let affected = match query.todos {
+        Some(DeletedProjectTodos::Cascade) => sqlx::query!(
+            "
+                DELETE FROM todo
+                WHERE project_id = $1
+            ",
+            project_id
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(map_delete_project_error)?
+        .rows_affected(),
+        Some(DeletedProjectTodos::Detach) => sqlx::query!(
+            "
+                UPDATE todo
+                SET project_id = NULL,
+                    project_todo_number = NULL,
+                    sort_rank = inbox.max_sort_rank + detached.position * $3
+                FROM (
+                    SELECT id, ROW_NUMBER() OVER (ORDER BY sort_rank, id) AS position FROM todo
+                    WHERE project_id = $1
+                ) detached, (
+                    SELECT COALESCE(MAX(sort_rank), 0) AS max_sort_rank FROM todo
+                    WHERE account_id = $2 AND project_id IS NULL
+                ) inbox
+                WHERE todo.id = detached.id
+            ",
+            project_id,
+            account_id,
+            RANK_GAP
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(map_delete_project_error)?
+        .rows_affected(),
+        None => {
+            sqlx::query!(
+                "SELECT COUNT(*) AS \"count!\" FROM todo WHERE project_id = $1",
+                project_id
+            )
+            .fetch_one(&mut transaction)
+            .await
+            .map_err(map_delete_project_error)?
+            .count as u64
+        }
+    };
+
+    if query.todos.is_none() && affected > 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::CONFLICT,
+            "The project has todos. Choose whether to delete or detach them.",
+        ));
+    }
+
+    sqlx::query!(
+        "
+            DELETE FROM project
+            WHERE id = $1 AND account_id = $2
+        ",
+        project_id,
+        account_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_delete_project_error)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(map_delete_project_error)?;
+
+    Ok(().into())
 }
@@ -0,0 +1,571 @@
+use axum::http::StatusCode;
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Weekday};
+use sqlx::postgres::Postgres;
+use sqlx::Transaction;
+
+use super::{ordering::RANK_GAP, APIResult, ErrorResponse};
+
+/// Occurrences searched before giving up on a rule that never matches, such
+/// as the 31st of every 12th month starting in February.
+const MAX_RECURRENCE_STEPS: u32 = 1000;
+
+/// The largest supported `INTERVAL`, which keeps date arithmetic in range.
+const MAX_RECURRENCE_INTERVAL: u32 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// The supported subset of an RFC 5545 recurrence rule.
+pub(super) struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    /// Days of the week, for weekly rules.
+    by_day: Vec<Weekday>,
+    /// Days of the month, for monthly rules. Negative days count from the end
+    /// of the month.
+    by_month_day: Vec<i32>,
+    until: Option<NaiveDate>,
+}
+
+fn invalid_recurrence(message: &'static str) -> ErrorResponse {
+    ErrorResponse::from(StatusCode::BAD_REQUEST, message)
+}
+
+fn parse_weekday(weekday: &str) -> Option<Weekday> {
+    match weekday {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Parses an `UNTIL` value, either a date (`YYYYMMDD`) or a UTC date-time
+/// (`YYYYMMDDTHHMMSSZ`), keeping the date.
+fn parse_until(until: &str) -> Option<NaiveDate> {
+    let until = until.to_ascii_uppercase();
+    // The parser also accepts signs and shorter fields, so the digits are
+    // checked first.
+    let is_well_formed = until.char_indices().all(|(index, char)| match index {
+        8 => char == 'T',
+        15 => char == 'Z',
+        _ => char.is_ascii_digit(),
+    });
+    if !is_well_formed {
+        return None;
+    }
+
+    match until.len() {
+        8 => NaiveDate::parse_from_str(&until, "%Y%m%d").ok(),
+        16 => NaiveDateTime::parse_from_str(&until, "%Y%m%dT%H%M%SZ")
+            .ok()
+            .map(|datetime| datetime.date()),
+        _ => None,
+    }
+}
+
+impl Recurrence {
+    pub(super) fn parse(rule: &str) -> APIResult<Recurrence> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+
+        let mut frequency = None;
+        let mut recurrence = Recurrence {
+            frequency: Frequency::Daily,
+            interval: 1,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            until: None,
+        };
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid_recurrence("Invalid recurrence rule."))?;
+
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(invalid_recurrence("Unsupported recurrence frequency.")),
+                    })
+                }
+                "INTERVAL" => {
+                    recurrence.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| (1..=MAX_RECURRENCE_INTERVAL).contains(interval))
+                        .ok_or_else(|| invalid_recurrence("Invalid recurrence interval."))?
+                }
+                "BYDAY" => {
+                    recurrence.by_day = value
+                        .split(',')
+                        .map(|weekday| parse_weekday(&weekday.to_ascii_uppercase()))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| invalid_recurrence("Invalid recurrence weekday."))?
+                }
+                "BYMONTHDAY" => {
+                    recurrence.by_month_day = value
+                        .split(',')
+                        .map(|day| day.parse::<i32>().ok())
+                        .map(|day| day.filter(|day| *day != 0 && (-31..=31).contains(day)))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| invalid_recurrence("Invalid recurrence day of month."))?
+                }
+                "UNTIL" => {
+                    recurrence.until = Some(
+                        parse_until(value)
+                            .ok_or_else(|| invalid_recurrence("Invalid recurrence end."))?,
+                    )
+                }
+                _ => return Err(invalid_recurrence("Unsupported recurrence rule part.")),
+            }
+        }
+
+        recurrence.frequency =
+            frequency.ok_or_else(|| invalid_recurrence("The recurrence rule has no frequency."))?;
+
+        if !recurrence.by_day.is_empty() && recurrence.frequency != Frequency::Weekly {
+            return Err(invalid_recurrence(
+                "BYDAY is only supported for weekly recurrence.",
+            ));
+        }
+        if !recurrence.by_month_day.is_empty() && recurrence.frequency != Frequency::Monthly {
+            return Err(invalid_recurrence(
+                "BYMONTHDAY is only supported for monthly recurrence.",
+            ));
+        }
+
+        Ok(recurrence)
+    }
+
+    /// The first occurrence strictly after the given occurrence, or `None` if
+    /// the recurrence has ended.
+    pub(super) fn next_after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let next = match self.frequency {
+            Frequency::Daily => date.checked_add_signed(Duration::days(self.interval.into())),
+            Frequency::Weekly => self.next_weekly(date),
+            Frequency::Monthly => self.next_monthly(date),
+            Frequency::Yearly => (1..MAX_RECURRENCE_STEPS).find_map(|step| {
+                let years = i32::try_from(step.checked_mul(self.interval)?).ok()?;
+                date.with_year(date.year().checked_add(years)?)
+            }),
+        }?;
+
+        self.before_end(next)
+    }
+
+    /// The occurrence one interval after the given date, ignoring the days
+    /// the rule is limited to. Used for recurrence from completion.
+    pub(super) fn advance(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let next = match self.frequency {
+            Frequency::Daily => date.checked_add_signed(Duration::days(self.interval.into())),
+            Frequency::Weekly => date.checked_add_signed(Duration::weeks(self.interval.into())),
+            Frequency::Monthly => date.checked_add_months(Months::new(self.interval)),
+            Frequency::Yearly => {
+                date.checked_add_months(Months::new(self.interval.checked_mul(12)?))
+            }
+        }?;
+
+        self.before_end(next)
+    }
+
+    fn before_end(&self, date: NaiveDate) -> Option<NaiveDate> {
+        self.until.is_none_or(|until| date <= until).then_some(date)
+    }
+
+    fn next_weekly(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let mut weekdays = if self.by_day.is_empty() {
+            vec![date.weekday()]
+        } else {
+            self.by_day.clone()
+        };
+        weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+
+        let week_start =
+            date.checked_sub_signed(Duration::days(date.weekday().num_days_from_monday().into()))?;
+        let later_this_week = weekdays
+            .iter()
+            .filter_map(|weekday| {
+                week_start.checked_add_signed(Duration::days(weekday.num_days_from_monday().into()))
+            })
+            .find(|candidate| *candidate > date);
+
+        later_this_week.or_else(|| {
+            week_start
+                .checked_add_signed(Duration::weeks(self.interval.into()))?
+                .checked_add_signed(Duration::days(weekdays[0].num_days_from_monday().into()))
+        })
+    }
+
+    fn next_monthly(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let month_days = if self.by_month_day.is_empty() {
+            vec![date.day() as i32]
+        } else {
+            self.by_month_day.clone()
+        };
+        let month_start = date.with_day(1)?;
+
+        (0..MAX_RECURRENCE_STEPS).find_map(|step| {
+            let month =
+                month_start.checked_add_months(Months::new(step.checked_mul(self.interval)?))?;
+            let days_in_month = month.checked_add_months(Months::new(1))?.pred_opt()?.day() as i32;
+
+            let mut candidates = month_days
+                .iter()
+                .map(|day| {
+                    if *day < 0 {
+                        days_in_month + day + 1
+                    } else {
+                        *day
+                    }
+                })
+                .filter(|day| (1..=days_in_month).contains(day))
+                .filter_map(|day| month.with_day(day as u32))
+                .filter(|candidate| *candidate > date)
+                .collect::<Vec<_>>();
+            candidates.sort();
+            candidates.first().copied()
+        })
+    }
+}
+
+/// Creates the next occurrence of a completed recurring todo, unless the todo
+/// does not recur, its recurrence has ended, or its next occurrence already
+/// exists. Tags, checklist items, and reminders relative to the deadline are
+/// copied, with the checklist unchecked. The next occurrence stays in the
+/// section and milestone of the todo, but not in its iteration, which covers
+/// the period of the completed occurrence only.
+///
+/// The dates of the todo are moved to the next occurrence in the time zone of
+/// the user, keeping the time of day of a timed deadline.
+pub(super) async fn create_next_occurrence(
+    transaction: &mut Transaction<'_, Postgres>,
+    todo_id: i64,
+) -> APIResult<()> {
+    let todo = sqlx::query!(
+        "
+            SELECT
+                todo.recurrence,
+                todo.recurs_from_completion,
+                (todo.deadline AT TIME ZONE 'UTC' AT TIME ZONE account.time_zone)::DATE
+                    AS local_deadline_date,
+                todo.deadline_date,
+                todo.scheduled_date,
+                todo.start_date,
+                (todo.completed_at AT TIME ZONE 'UTC' AT TIME ZONE account.time_zone)::DATE
+                    AS local_completed_date,
+                EXISTS (
+                    SELECT 1 FROM todo next WHERE next.previous_occurrence_todo_id = todo.id
+                ) AS \"has_next_occurrence!\"
+            FROM todo
+            JOIN account ON account.id = todo.account_id
+            WHERE todo.id = $1
+        ",
+        todo_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch recurrence.",
+        )
+    })?;
+
+    let (recurrence, completed_date) = match (todo.recurrence, todo.local_completed_date) {
+        (Some(recurrence), Some(completed_date)) if !todo.has_next_occurrence => {
+            (Recurrence::parse(&recurrence)?, completed_date)
+        }
+        _ => return Ok(()),
+    };
+
+    // The date the occurrence is anchored to. All dates of the todo move by
+    // the same number of days.
+    let anchor_date = todo
+        .local_deadline_date
+        .or(todo.deadline_date)
+        .or(todo.scheduled_date)
+        .or(todo.start_date);
+    let next_date = if todo.recurs_from_completion {
+        recurrence.advance(completed_date)
+    } else {
+        recurrence.next_after(anchor_date.unwrap_or(completed_date))
+    };
+    let next_date = match next_date {
+        Some(next_date) => next_date,
+        None => return Ok(()),
+    };
+    let shift_days = (next_date - anchor_date.unwrap_or(completed_date)).num_days() as i32;
+
+    let next_todo_id = sqlx::query!(
+        "
+            INSERT INTO todo (
+                account_id, title, memo, deadline, deadline_date, start_date, scheduled_date,
+                project_id, project_todo_number, parent_todo_id, priority, sort_rank,
+                recurrence, recurs_from_completion, previous_occurrence_todo_id, section_id,
+                milestone_id
+            )
+            SELECT
+                todo.account_id,
+                todo.title,
+                todo.memo,
+                (
+                    (todo.deadline AT TIME ZONE 'UTC' AT TIME ZONE account.time_zone) + $2::INT * INTERVAL '1 day'
+                ) AT TIME ZONE account.time_zone AT TIME ZONE 'UTC',
+                CASE WHEN $4 THEN $3 ELSE todo.deadline_date + $2 END,
+                todo.start_date + $2,
+                todo.scheduled_date + $2,
+                todo.project_id,
+                CASE WHEN todo.project_id IS NULL THEN NULL ELSE allocate_project_todo_number(todo.project_id) END,
+                todo.parent_todo_id,
+                todo.priority,
+                (
+                    SELECT COALESCE(MAX(list.sort_rank), 0) + $5 FROM todo list
+                    WHERE list.account_id = todo.account_id
+                        AND list.project_id IS NOT DISTINCT FROM todo.project_id
+                ),
+                todo.recurrence,
+                todo.recurs_from_completion,
+                todo.id,
+                todo.section_id,
+                todo.milestone_id
+            FROM todo
+            JOIN account ON account.id = todo.account_id
+            WHERE todo.id = $1
+            RETURNING id
+        ",
+        todo_id,
+        shift_days,
+        next_date,
+        anchor_date.is_none(),
+        RANK_GAP
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map(|record| record.id)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create next occurrence.",
+        )
+    })?;
+
+    sqlx::query!(
+        "
+            INSERT INTO todo_tag (todo_id, tag_id)
+            SELECT $2, tag_id FROM todo_tag WHERE todo_id = $1
+        ",
+        todo_id,
+        next_todo_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create next occurrence.",
+        )
+    })?;
+
+    sqlx::query!(
+        "
+            INSERT INTO checklist_item (todo_id, position, text)
+            SELECT $2, position, text FROM checklist_item WHERE todo_id = $1
+        ",
+        todo_id,
+        next_todo_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create next occurrence.",
+        )
+    })?;
+
+    sqlx::query!(
+        "
+            INSERT INTO reminder (todo_id, minutes_before_deadline, channel)
+            SELECT $2, minutes_before_deadline, channel FROM reminder
+            WHERE todo_id = $1 AND minutes_before_deadline IS NOT NULL
+        ",
+        todo_id,
+        next_todo_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create next occurrence.",
+        )
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(rule: &str) -> Recurrence {
+        Recurrence::parse(rule).unwrap_or_else(|_| panic!("{} should be valid", rule))
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parse_accepts_supported_rules() {
+        let recurrence = rule("RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,fr;UNTIL=20221231T000000Z");
+        assert!(recurrence.frequency == Frequency::Weekly);
+        assert_eq!(recurrence.interval, 2);
+        assert_eq!(recurrence.by_day, vec![Weekday::Mon, Weekday::Fri]);
+        assert_eq!(recurrence.until, Some(date(2022, 12, 31)));
+
+        let recurrence = rule("FREQ=DAILY;UNTIL=20221231");
+        assert_eq!(recurrence.until, Some(date(2022, 12, 31)));
+
+        let recurrence = rule("freq=monthly;bymonthday=1,-1");
+        assert!(recurrence.frequency == Frequency::Monthly);
+        assert_eq!(recurrence.by_month_day, vec![1, -1]);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_rules() {
+        for invalid in [
+            "",
+            "INTERVAL=2",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;INTERVAL=1001",
+            "FREQ=DAILY;INTERVAL=4294967295",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=WEEKLY;BYDAY=XX",
+            "FREQ=WEEKLY;BYMONTHDAY=1",
+            "FREQ=MONTHLY;BYMONTHDAY=0",
+            "FREQ=MONTHLY;BYMONTHDAY=32",
+            "FREQ=DAILY;UNTIL=tomorrow",
+            "FREQ=DAILY;UNTIL=20221231garbage",
+            "FREQ=DAILY;UNTIL=2022123",
+            "FREQ=DAILY;UNTIL=+2022123",
+            "FREQ=DAILY;UNTIL=20221231T000000",
+            "FREQ=DAILY;UNTIL=20221231T250000Z",
+            "FREQ=DAILY;UNTIL=20221332",
+            "FREQ=DAILY;COUNT=3",
+            "FREQ",
+        ] {
+            assert!(
+                Recurrence::parse(invalid).is_err(),
+                "{} should be invalid",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn next_after_daily() {
+        assert_eq!(
+            rule("FREQ=DAILY;INTERVAL=3").next_after(date(2022, 12, 30)),
+            Some(date(2023, 1, 2))
+        );
+    }
+
+    #[test]
+    fn next_after_weekly_by_day() {
+        let recurrence = rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH");
+
+        // Monday 2022-10-31 is followed by Thursday of the same week.
+        assert_eq!(
+            recurrence.next_after(date(2022, 10, 31)),
+            Some(date(2022, 11, 3))
+        );
+        // After Thursday, the next occurrence is Monday two weeks later.
+        assert_eq!(
+            recurrence.next_after(date(2022, 11, 3)),
+            Some(date(2022, 11, 14))
+        );
+    }
+
+    #[test]
+    fn next_after_monthly_skips_missing_days() {
+        let recurrence = rule("FREQ=MONTHLY;BYMONTHDAY=31");
+
+        assert_eq!(
+            recurrence.next_after(date(2022, 1, 31)),
+            Some(date(2022, 3, 31))
+        );
+    }
+
+    #[test]
+    fn next_after_monthly_counts_negative_days_from_the_end() {
+        let recurrence = rule("FREQ=MONTHLY;BYMONTHDAY=-1");
+
+        assert_eq!(
+            recurrence.next_after(date(2022, 1, 31)),
+            Some(date(2022, 2, 28))
+        );
+    }
+
+    #[test]
+    fn next_after_yearly_skips_missing_leap_days() {
+        assert_eq!(
+            rule("FREQ=YEARLY").next_after(date(2020, 2, 29)),
+            Some(date(2024, 2, 29))
+        );
+    }
+
+    #[test]
+    fn next_after_respects_until() {
+        let recurrence = rule("FREQ=DAILY;UNTIL=20221102");
+
+        assert_eq!(
+            recurrence.next_after(date(2022, 11, 1)),
+            Some(date(2022, 11, 2))
+        );
+        assert_eq!(recurrence.next_after(date(2022, 11, 2)), None);
+    }
+
+    #[test]
+    fn advance_ignores_the_limited_days() {
+        assert_eq!(
+            rule("FREQ=WEEKLY;BYDAY=MO").advance(date(2022, 11, 3)),
+            Some(date(2022, 11, 10))
+        );
+        assert_eq!(
+            rule("FREQ=MONTHLY;INTERVAL=1").advance(date(2022, 1, 31)),
+            Some(date(2022, 2, 28))
+        );
+        assert_eq!(
+            rule("FREQ=YEARLY;INTERVAL=2").advance(date(2022, 11, 3)),
+            Some(date(2024, 11, 3))
+        );
+    }
+
+    #[test]
+    fn large_intervals_end_the_recurrence_instead_of_overflowing() {
+        let last_date = NaiveDate::MAX;
+
+        for frequency in ["DAILY", "WEEKLY", "MONTHLY", "YEARLY"] {
+            let recurrence = rule(&format!("FREQ={};INTERVAL=1000", frequency));
+
+            assert_eq!(recurrence.next_after(last_date), None, "{}", frequency);
+            assert_eq!(recurrence.advance(last_date), None, "{}", frequency);
+        }
+    }
+}
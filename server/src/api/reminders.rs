@@ -0,0 +1,438 @@
+use axum::{
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+
+use crate::auth::AccountId;
+
+use super::{
+    deserialize_optional_field,
+    todos::{validate_account_can_edit_todo, validate_account_has_todo},
+    APIResponse, APIResult, ErrorResponse,
+};
+
+/// How a reminder is delivered.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderChannel {
+    /// As a notification inside the app.
+    #[default]
+    InApp,
+    /// By email to the address of the user.
+    Email,
+    /// By a POST request to the webhook URL of the user.
+    Webhook,
+}
+
+impl ReminderChannel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReminderChannel::InApp => "in_app",
+            ReminderChannel::Email => "email",
+            ReminderChannel::Webhook => "webhook",
+        }
+    }
+
+    fn parse(channel: &str) -> Option<ReminderChannel> {
+        match channel {
+            "in_app" => Some(ReminderChannel::InApp),
+            "email" => Some(ReminderChannel::Email),
+            "webhook" => Some(ReminderChannel::Webhook),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PublicReminder {
+    id: i64,
+    todo_id: i64,
+    remind_at: Option<DateTime<Utc>>,
+    minutes_before_deadline: Option<i32>,
+    channel: ReminderChannel,
+    /// When the reminder is due, or `None` if it is relative to the deadline
+    /// and the todo has no deadline.
+    fire_at: Option<DateTime<Utc>>,
+    sent_at: Option<DateTime<Utc>>,
+    attempts: i32,
+    /// The error of the last failed delivery.
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct ReminderRecord {
+    id: i64,
+    todo_id: i64,
+    remind_at: Option<NaiveDateTime>,
+    minutes_before_deadline: Option<i32>,
+    channel: String,
+    fire_at: Option<NaiveDateTime>,
+    sent_at: Option<NaiveDateTime>,
+    attempts: i32,
+    last_error: Option<String>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl From<ReminderRecord> for PublicReminder {
+    fn from(record: ReminderRecord) -> Self {
+        PublicReminder {
+            id: record.id,
+            todo_id: record.todo_id,
+            remind_at: record
+                .remind_at
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+            minutes_before_deadline: record.minutes_before_deadline,
+            channel: ReminderChannel::parse(&record.channel).unwrap_or_default(),
+            fire_at: record
+                .fire_at
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+            sent_at: record
+                .sent_at
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+            attempts: record.attempts,
+            last_error: record.last_error,
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+pub async fn get_reminders(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+) -> APIResponse<Vec<PublicReminder>> {
+    validate_account_has_todo(&pg_pool, account_id, todo_id).await?;
+
+    Ok(sqlx::query_as!(
+        ReminderRecord,
+        "
+            SELECT
+                id, todo_id, remind_at, minutes_before_deadline, channel,
+                reminder_fire_at(id) AS fire_at,
+                sent_at, attempts, last_error, created_at, updated_at
+            FROM reminder
+            WHERE todo_id = $1
+            ORDER BY reminder_fire_at(id), id
+        ",
+        todo_id
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch reminders.",
+        )
+    })?
+    .into_iter()
+    .map(PublicReminder::from)
+    .collect::<Vec<_>>()
+    .into())
+}
+
+/// A reminder at `remind_at`, or `minutes_before_deadline` minutes before the
+/// deadline of the todo. Exactly one of the two has to be given.
+#[derive(Deserialize)]
+pub struct CreateReminderRequest {
+    remind_at: Option<DateTime<Utc>>,
+    minutes_before_deadline: Option<i32>,
+    #[serde(default)]
+    channel: ReminderChannel,
+}
+
+/// Partial update of a reminder. Setting one of `remind_at` and
+/// `minutes_before_deadline` clears the other. Changing a reminder schedules
+/// it again, even if it was already sent.
+#[derive(Deserialize)]
+pub struct UpdateReminderRequest {
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    remind_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    minutes_before_deadline: Option<Option<i32>>,
+    channel: Option<ReminderChannel>,
+}
+
+/// Schedules the reminders of a todo that are relative to its deadline again,
+/// after the deadline changed. Reminders whose new time has already passed
+/// are left as they are.
+pub(super) async fn reschedule_deadline_reminders(
+    transaction: &mut Transaction<'_, Postgres>,
+    todo_id: i64,
+) -> APIResult<()> {
+    sqlx::query!(
+        "
+            UPDATE reminder
+            SET sent_at = NULL, attempts = 0, retry_at = NULL, last_error = NULL
+            WHERE todo_id = $1
+                AND minutes_before_deadline IS NOT NULL
+                AND reminder_fire_at(id) > CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+        ",
+        todo_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to reschedule reminders.",
+        )
+    })?;
+
+    Ok(())
+}
+
+fn validate_reminder_time(
+    remind_at: Option<DateTime<Utc>>,
+    minutes_before_deadline: Option<i32>,
+) -> APIResult<()> {
+    if remind_at.is_some() == minutes_before_deadline.is_some() {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "A reminder needs either a time or a number of minutes before the deadline.",
+        ));
+    }
+
+    Ok(())
+}
+
+async fn fetch_reminder(pg_pool: &PgPool, reminder_id: i64) -> APIResult<PublicReminder> {
+    sqlx::query_as!(
+        ReminderRecord,
+        "
+            SELECT
+                id, todo_id, remind_at, minutes_before_deadline, channel,
+                reminder_fire_at(id) AS fire_at,
+                sent_at, attempts, last_error, created_at, updated_at
+            FROM reminder
+            WHERE id = $1
+        ",
+        reminder_id
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map(PublicReminder::from)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch reminder.",
+        )
+    })
+}
+
+pub async fn post_reminders(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+    Json(req): Json<CreateReminderRequest>,
+) -> APIResponse<PublicReminder> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+    validate_reminder_time(req.remind_at, req.minutes_before_deadline)?;
+
+    let reminder_id = sqlx::query!(
+        "
+            INSERT INTO reminder (todo_id, remind_at, minutes_before_deadline, channel)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+        ",
+        todo_id,
+        req.remind_at.map(|datetime| datetime.naive_utc()),
+        req.minutes_before_deadline,
+        req.channel.as_str()
+    )
+    .fetch_one(&pg_pool)
+    .await
+    .map(|record| record.id)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create reminder.",
+        )
+    })?;
+
+    Ok(fetch_reminder(&pg_pool, reminder_id).await?.into())
+}
+
+pub async fn post_reminder(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((todo_id, reminder_id)): Path<(i64, i64)>,
+    Json(req): Json<UpdateReminderRequest>,
+) -> APIResponse<PublicReminder> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    if req.remind_at.is_some() || req.minutes_before_deadline.is_some() {
+        validate_reminder_time(
+            req.remind_at.flatten(),
+            req.minutes_before_deadline.flatten(),
+        )?;
+    }
+
+    let updated = sqlx::query!(
+        "
+            UPDATE reminder
+            SET remind_at = CASE WHEN $3 OR $5 THEN $4 ELSE remind_at END,
+                minutes_before_deadline =
+                    CASE WHEN $3 OR $5 THEN $6 ELSE minutes_before_deadline END,
+                channel = COALESCE($7, channel),
+                sent_at = NULL,
+                attempts = 0,
+                retry_at = NULL,
+                last_error = NULL
+            WHERE id = $1 AND todo_id = $2
+        ",
+        reminder_id,
+        todo_id,
+        req.remind_at.is_some(),
+        req.remind_at.flatten().map(|datetime| datetime.naive_utc()),
+        req.minutes_before_deadline.is_some(),
+        req.minutes_before_deadline.flatten(),
+        req.channel.map(|channel| channel.as_str())
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update reminder.",
+        )
+    })?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The reminder does not exist.",
+        ));
+    }
+
+    Ok(fetch_reminder(&pg_pool, reminder_id).await?.into())
+}
+
+pub async fn delete_reminder(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((todo_id, reminder_id)): Path<(i64, i64)>,
+) -> APIResponse<()> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let deleted = sqlx::query!(
+        "
+            DELETE FROM reminder
+            WHERE id = $1 AND todo_id = $2
+        ",
+        reminder_id,
+        todo_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete reminder.",
+        )
+    })?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The reminder does not exist.",
+        ));
+    }
+
+    Ok(().into())
+}
+
+#[derive(Serialize)]
+pub struct PublicNotification {
+    id: i64,
+    todo_id: Option<i64>,
+    message: String,
+    read_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct GetNotificationsQuery {
+    /// Only return notifications that are not read yet.
+    #[serde(default)]
+    unread: bool,
+}
+
+pub async fn get_notifications(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Query(query): Query<GetNotificationsQuery>,
+) -> APIResponse<Vec<PublicNotification>> {
+    Ok(sqlx::query!(
+        "
+            SELECT id, todo_id, message, read_at, created_at FROM notification
+            WHERE account_id = $1 AND (NOT $2 OR read_at IS NULL)
+            ORDER BY created_at DESC, id DESC
+        ",
+        account_id,
+        query.unread
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch notifications.",
+        )
+    })?
+    .into_iter()
+    .map(|record| PublicNotification {
+        id: record.id,
+        todo_id: record.todo_id,
+        message: record.message,
+        read_at: record
+            .read_at
+            .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+        created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+    })
+    .collect::<Vec<_>>()
+    .into())
+}
+
+pub async fn post_notification_read(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(notification_id): Path<i64>,
+) -> APIResponse<()> {
+    let updated = sqlx::query!(
+        "
+            UPDATE notification
+            SET read_at = COALESCE(read_at, CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+            WHERE id = $1 AND account_id = $2
+        ",
+        notification_id,
+        account_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update notification.",
+        )
+    })?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The notification does not exist.",
+        ));
+    }
+
+    Ok(().into())
+}
@@ -0,0 +1,302 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+use crate::auth::AccountId;
+
+use super::{
+    todos::{validate_account_can_edit_project, validate_account_has_project},
+    APIResponse, APIResult, ErrorResponse,
+};
+
+#[derive(Serialize)]
+pub struct PublicSection {
+    id: i64,
+    project_id: i64,
+    section_name: String,
+    position: i32,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct SectionRecord {
+    id: i64,
+    project_id: i64,
+    section_name: String,
+    position: i32,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl From<SectionRecord> for PublicSection {
+    fn from(record: SectionRecord) -> Self {
+        PublicSection {
+            id: record.id,
+            project_id: record.project_id,
+            section_name: record.section_name,
+            position: record.position,
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+/// Checks that a section belongs to the project a todo is in or is moved to.
+pub(super) async fn validate_section(
+    pg_pool: &PgPool,
+    section_id: i64,
+    project_id: Option<i64>,
+) -> APIResult<()> {
+    let exists = sqlx::query!(
+        "
+            SELECT EXISTS (
+                SELECT 1 FROM project_section WHERE id = $1 AND project_id = $2
+            ) AS \"exists!\"
+        ",
+        section_id,
+        project_id
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map(|record| record.exists)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate section.",
+        )
+    })?;
+
+    if !exists {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The section does not belong to the project of the todo.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fetches the sections of each of the given projects, in their order.
+pub(super) async fn fetch_sections_by_project_id(
+    pg_pool: &PgPool,
+    project_ids: &[i64],
+) -> APIResult<HashMap<i64, Vec<PublicSection>>> {
+    let mut sections_by_project_id = HashMap::<i64, Vec<PublicSection>>::new();
+
+    let records = sqlx::query_as!(
+        SectionRecord,
+        "
+            SELECT * FROM project_section
+            WHERE project_id = ANY($1)
+            ORDER BY position, id
+        ",
+        project_ids
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch sections.",
+        )
+    })?;
+
+    for record in records {
+        sections_by_project_id
+            .entry(record.project_id)
+            .or_default()
+            .push(PublicSection::from(record));
+    }
+
+    Ok(sections_by_project_id)
+}
+
+async fn fetch_sections(pg_pool: &PgPool, project_id: i64) -> APIResult<Vec<PublicSection>> {
+    Ok(fetch_sections_by_project_id(pg_pool, &[project_id])
+        .await?
+        .remove(&project_id)
+        .unwrap_or_default())
+}
+
+pub async fn get_sections(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+) -> APIResponse<Vec<PublicSection>> {
+    validate_account_has_project(&pg_pool, account_id, project_id).await?;
+
+    Ok(fetch_sections(&pg_pool, project_id).await?.into())
+}
+
+#[derive(Deserialize)]
+pub struct CreateSectionRequest {
+    section_name: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateSectionRequest {
+    section_name: String,
+}
+
+/// The new order of the sections of a project, containing every section
+/// exactly once.
+#[derive(Deserialize)]
+pub struct ReorderSectionsRequest {
+    section_ids: Vec<i64>,
+}
+
+pub async fn post_sections(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Json(req): Json<CreateSectionRequest>,
+) -> APIResponse<PublicSection> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let section = sqlx::query_as!(
+        SectionRecord,
+        "
+            INSERT INTO project_section (project_id, section_name, position)
+            VALUES (
+                $1,
+                $2,
+                (SELECT COALESCE(MAX(position), -1) + 1 FROM project_section WHERE project_id = $1)
+            )
+            RETURNING *
+        ",
+        project_id,
+        req.section_name.trim()
+    )
+    .fetch_one(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create section.",
+        )
+    })?;
+
+    Ok(PublicSection::from(section).into())
+}
+
+pub async fn put_sections(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Json(req): Json<ReorderSectionsRequest>,
+) -> APIResponse<Vec<PublicSection>> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let current_section_ids = fetch_sections(&pg_pool, project_id)
+        .await?
+        .into_iter()
+        .map(|section| section.id)
+        .collect::<HashSet<_>>();
+    let requested_section_ids = req.section_ids.iter().copied().collect::<HashSet<_>>();
+
+    if requested_section_ids.len() != req.section_ids.len()
+        || requested_section_ids != current_section_ids
+    {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The order must contain every section of the project exactly once.",
+        ));
+    }
+
+    sqlx::query!(
+        "
+            UPDATE project_section
+            SET position = ordering.position - 1
+            FROM UNNEST($2::BIGINT[]) WITH ORDINALITY AS ordering(id, position)
+            WHERE project_section.id = ordering.id AND project_section.project_id = $1
+        ",
+        project_id,
+        &req.section_ids
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to reorder sections.",
+        )
+    })?;
+
+    Ok(fetch_sections(&pg_pool, project_id).await?.into())
+}
+
+pub async fn post_section(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, section_id)): Path<(i64, i64)>,
+    Json(req): Json<UpdateSectionRequest>,
+) -> APIResponse<PublicSection> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let section = sqlx::query_as!(
+        SectionRecord,
+        "
+            UPDATE project_section
+            SET section_name = $3
+            WHERE id = $1 AND project_id = $2
+            RETURNING *
+        ",
+        section_id,
+        project_id,
+        req.section_name.trim()
+    )
+    .fetch_optional(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update section.",
+        )
+    })?
+    .ok_or_else(|| ErrorResponse::from(StatusCode::NOT_FOUND, "The section does not exist."))?;
+
+    Ok(PublicSection::from(section).into())
+}
+
+/// Deletes a section. Its todos stay in the project without a section.
+pub async fn delete_section(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, section_id)): Path<(i64, i64)>,
+) -> APIResponse<()> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let deleted = sqlx::query!(
+        "
+            DELETE FROM project_section
+            WHERE id = $1 AND project_id = $2
+        ",
+        section_id,
+        project_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete section.",
+        )
+    })?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The section does not exist.",
+        ));
+    }
+
+    Ok(().into())
+}
@@ -0,0 +1,285 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+
+use crate::auth::AccountId;
+
+use super::{
+    reminders::reschedule_deadline_reminders,
+    todos::{
+        fetch_todo, todo_response, validate_account_can_edit_todo, validate_account_has_todo,
+        validate_single_deadline, PublicTodo,
+    },
+    APIResponse, APIResult, ErrorResponse,
+};
+
+#[derive(Serialize)]
+pub struct PublicTodoSnooze {
+    id: i64,
+    todo_id: i64,
+    snoozed_until: DateTime<Utc>,
+    /// The deadline before and after snoozing, if the snooze moved it.
+    previous_deadline: Option<DateTime<Utc>>,
+    previous_deadline_date: Option<NaiveDate>,
+    deadline: Option<DateTime<Utc>>,
+    deadline_date: Option<NaiveDate>,
+    /// Whether the user is notified when the snooze ends.
+    notify: bool,
+    /// When the todo was woken up, or snoozed again, before the snooze ended.
+    cancelled_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+struct TodoSnoozeRecord {
+    id: i64,
+    todo_id: i64,
+    snoozed_until: NaiveDateTime,
+    previous_deadline: Option<NaiveDateTime>,
+    previous_deadline_date: Option<NaiveDate>,
+    deadline: Option<NaiveDateTime>,
+    deadline_date: Option<NaiveDate>,
+    notify: bool,
+    #[allow(dead_code)]
+    reminder_id: Option<i64>,
+    cancelled_at: Option<NaiveDateTime>,
+    created_at: NaiveDateTime,
+}
+
+impl From<TodoSnoozeRecord> for PublicTodoSnooze {
+    fn from(record: TodoSnoozeRecord) -> Self {
+        PublicTodoSnooze {
+            id: record.id,
+            todo_id: record.todo_id,
+            snoozed_until: DateTime::<Utc>::from_naive_utc_and_offset(record.snoozed_until, Utc),
+            previous_deadline: record
+                .previous_deadline
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+            previous_deadline_date: record.previous_deadline_date,
+            deadline: record
+                .deadline
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+            deadline_date: record.deadline_date,
+            notify: record.notify,
+            cancelled_at: record
+                .cancelled_at
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+        }
+    }
+}
+
+/// Hides a todo until `until`. The deadline is moved to `deadline` or
+/// `deadline_date` if one of them is given. With `notify`, an in-app reminder
+/// is sent when the snooze ends.
+#[derive(Deserialize)]
+pub struct SnoozeTodoRequest {
+    until: DateTime<Utc>,
+    deadline: Option<DateTime<Utc>>,
+    deadline_date: Option<NaiveDate>,
+    #[serde(default)]
+    notify: bool,
+}
+
+fn map_snooze_error(_err: sqlx::Error) -> ErrorResponse {
+    ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to snooze todo.")
+}
+
+/// Cancels the snooze of a todo that has not ended yet, together with its
+/// pending notification.
+async fn cancel_snooze(transaction: &mut Transaction<'_, Postgres>, todo_id: i64) -> APIResult<()> {
+    sqlx::query!(
+        "
+            WITH cancelled AS (
+                UPDATE todo_snooze
+                SET cancelled_at = CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+                WHERE todo_id = $1
+                    AND cancelled_at IS NULL
+                    AND snoozed_until > CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+                RETURNING reminder_id
+            )
+            DELETE FROM reminder
+            WHERE id IN (SELECT reminder_id FROM cancelled) AND sent_at IS NULL
+        ",
+        todo_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(map_snooze_error)?;
+
+    Ok(())
+}
+
+pub async fn get_todo_snoozes(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+) -> APIResponse<Vec<PublicTodoSnooze>> {
+    validate_account_has_todo(&pg_pool, account_id, todo_id).await?;
+
+    Ok(sqlx::query_as!(
+        TodoSnoozeRecord,
+        "
+            SELECT * FROM todo_snooze
+            WHERE todo_id = $1
+            ORDER BY created_at, id
+        ",
+        todo_id
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch snoozes.",
+        )
+    })?
+    .into_iter()
+    .map(PublicTodoSnooze::from)
+    .collect::<Vec<_>>()
+    .into())
+}
+
+pub async fn post_todo_snooze(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+    Json(req): Json<SnoozeTodoRequest>,
+) -> APIResponse<PublicTodo> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+    validate_single_deadline(req.deadline, req.deadline_date)?;
+
+    if req.until <= Utc::now() {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "A todo can only be snoozed until a time in the future.",
+        ));
+    }
+
+    let moves_deadline = req.deadline.is_some() || req.deadline_date.is_some();
+    let until = req.until.naive_utc();
+
+    let mut transaction = pg_pool.begin().await.map_err(map_snooze_error)?;
+
+    cancel_snooze(&mut transaction, todo_id).await?;
+
+    let previous = sqlx::query!(
+        "
+            SELECT deadline, deadline_date FROM todo
+            WHERE id = $1
+            FOR UPDATE
+        ",
+        todo_id
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .map_err(map_snooze_error)?;
+
+    sqlx::query!(
+        "
+            UPDATE todo
+            SET snoozed_until = $2,
+                snooze_count = snooze_count + 1,
+                deadline = CASE WHEN $3 THEN $4 ELSE deadline END,
+                deadline_date = CASE WHEN $3 THEN $5 ELSE deadline_date END
+            WHERE id = $1
+        ",
+        todo_id,
+        until,
+        moves_deadline,
+        req.deadline.map(|datetime| datetime.naive_utc()),
+        req.deadline_date
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_snooze_error)?;
+
+    if moves_deadline {
+        reschedule_deadline_reminders(&mut transaction, todo_id).await?;
+    }
+
+    let reminder_id = if req.notify {
+        Some(
+            sqlx::query!(
+                "
+                    INSERT INTO reminder (todo_id, remind_at, channel)
+                    VALUES ($1, $2, 'in_app')
+                    RETURNING id
+                ",
+                todo_id,
+                until
+            )
+            .fetch_one(&mut transaction)
+            .await
+            .map(|record| record.id)
+            .map_err(map_snooze_error)?,
+        )
+    } else {
+        None
+    };
+
+    sqlx::query!(
+        "
+            INSERT INTO todo_snooze (
+                todo_id, snoozed_until, previous_deadline, previous_deadline_date,
+                deadline, deadline_date, notify, reminder_id
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ",
+        todo_id,
+        until,
+        previous.deadline.filter(|_deadline| moves_deadline),
+        previous
+            .deadline_date
+            .filter(|_deadline_date| moves_deadline),
+        req.deadline.map(|datetime| datetime.naive_utc()),
+        req.deadline_date,
+        req.notify,
+        reminder_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_snooze_error)?;
+
+    transaction.commit().await.map_err(map_snooze_error)?;
+
+    let record = fetch_todo(&pg_pool, account_id, todo_id).await?;
+
+    todo_response(&pg_pool, record).await
+}
+
+/// Wakes a snoozed todo up before its snooze ends.
+pub async fn delete_todo_snooze(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+) -> APIResponse<PublicTodo> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let mut transaction = pg_pool.begin().await.map_err(map_snooze_error)?;
+
+    cancel_snooze(&mut transaction, todo_id).await?;
+
+    sqlx::query!(
+        "
+            UPDATE todo
+            SET snoozed_until = NULL
+            WHERE id = $1 AND snoozed_until IS NOT NULL
+        ",
+        todo_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_snooze_error)?;
+
+    transaction.commit().await.map_err(map_snooze_error)?;
+
+    let record = fetch_todo(&pg_pool, account_id, todo_id).await?;
+
+    todo_response(&pg_pool, record).await
+}
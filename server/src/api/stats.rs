@@ -0,0 +1,159 @@
+use axum::{
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+use crate::auth::AccountId;
+
+use super::{todos::validate_account_has_project, APIResponse, ErrorResponse};
+
+/// The default number of weeks covered by the weekly statistics.
+const DEFAULT_STATS_WEEKS: i32 = 12;
+/// The maximum number of weeks covered by the weekly statistics.
+const MAX_STATS_WEEKS: i32 = 104;
+
+#[derive(Serialize)]
+pub struct PublicProjectStats {
+    project_id: i64,
+    open_count: i64,
+    closed_count: i64,
+    /// The number of open todos whose deadline has passed.
+    overdue_count: i64,
+    /// The average time between creating and completing the completed todos,
+    /// or `None` if no todo is completed.
+    average_completion_seconds: Option<f64>,
+    /// The todos created and completed in each of the recent weeks, oldest
+    /// first.
+    weeks: Vec<PublicWeeklyStats>,
+}
+
+#[derive(Serialize)]
+pub struct PublicWeeklyStats {
+    /// The Monday starting the week, in the time zone of the account.
+    week_start: NaiveDate,
+    created_count: i64,
+    completed_count: i64,
+}
+
+#[derive(Deserialize)]
+pub struct GetProjectStatsQuery {
+    /// The number of weeks to return, up to and including the current week.
+    weeks: Option<i32>,
+}
+
+pub async fn get_project_stats(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Query(query): Query<GetProjectStatsQuery>,
+) -> APIResponse<PublicProjectStats> {
+    validate_account_has_project(&pg_pool, account_id, project_id).await?;
+
+    let week_count = query.weeks.unwrap_or(DEFAULT_STATS_WEEKS);
+    if !(1..=MAX_STATS_WEEKS).contains(&week_count) {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The number of weeks must be between 1 and 104.",
+        ));
+    }
+
+    let map_err = |_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch project statistics.",
+        )
+    };
+
+    let totals = sqlx::query!(
+        "
+            WITH local AS (
+                SELECT local_today(time_zone) AS today FROM account WHERE id = $2
+            )
+            SELECT
+                COUNT(*) FILTER (WHERE completed_at IS NULL) AS \"open_count!\",
+                COUNT(completed_at) AS \"closed_count!\",
+                COUNT(*) FILTER (WHERE completed_at IS NULL AND (
+                    deadline < CURRENT_TIMESTAMP AT TIME ZONE 'UTC' OR deadline_date < local.today
+                )) AS \"overdue_count!\",
+                AVG(EXTRACT(EPOCH FROM completed_at - created_at))::DOUBLE PRECISION
+                    AS average_completion_seconds
+            FROM todo, local
+            WHERE project_id = $1
+        ",
+        project_id,
+        account_id
+    )
+    .fetch_one(&pg_pool)
+    .await
+    .map_err(map_err)?;
+
+    let weeks = sqlx::query_as!(
+        PublicWeeklyStats,
+        "
+            WITH local AS (
+                SELECT
+                    time_zone,
+                    date_trunc('week', local_today(time_zone)::TIMESTAMP)::DATE - 7 * ($3 - 1)
+                        AS first_week_start
+                FROM account
+                WHERE id = $2
+            ),
+            period AS (
+                SELECT
+                    time_zone,
+                    first_week_start,
+                    local_day_start(first_week_start, time_zone) AS period_start
+                FROM local
+            ),
+            week AS (
+                SELECT period.first_week_start + 7 * weeks_ago AS week_start
+                FROM period, generate_series(0, $3 - 1) AS weeks_ago
+            ),
+            created AS (
+                SELECT
+                    date_trunc('week', created_at AT TIME ZONE 'UTC' AT TIME ZONE period.time_zone)::DATE
+                        AS week_start,
+                    COUNT(*) AS count
+                FROM todo, period
+                WHERE project_id = $1 AND created_at >= period.period_start
+                GROUP BY 1
+            ),
+            completed AS (
+                SELECT
+                    date_trunc('week', completed_at AT TIME ZONE 'UTC' AT TIME ZONE period.time_zone)::DATE
+                        AS week_start,
+                    COUNT(*) AS count
+                FROM todo, period
+                WHERE project_id = $1 AND completed_at >= period.period_start
+                GROUP BY 1
+            )
+            SELECT
+                week.week_start AS \"week_start!\",
+                COALESCE(created.count, 0) AS \"created_count!\",
+                COALESCE(completed.count, 0) AS \"completed_count!\"
+            FROM week
+            LEFT JOIN created USING (week_start)
+            LEFT JOIN completed USING (week_start)
+            ORDER BY week.week_start
+        ",
+        project_id,
+        account_id,
+        week_count
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(map_err)?;
+
+    Ok(PublicProjectStats {
+        project_id,
+        open_count: totals.open_count,
+        closed_count: totals.closed_count,
+        overdue_count: totals.overdue_count,
+        average_completion_seconds: totals.average_completion_seconds,
+        weeks,
+    }
+    .into())
+}
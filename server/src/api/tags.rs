@@ -0,0 +1,635 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+
+use crate::auth::AccountId;
+
+use super::{
+    deserialize_optional_field, is_unique_violation,
+    todos::{touch_todo, validate_account_can_edit_todo},
+    APIResponse, APIResult, ErrorResponse,
+};
+
+/// Separates the names of nested tags in a tag path, as in `work/clients/acme`.
+const TAG_PATH_SEPARATOR: char = '/';
+
+#[derive(Serialize, Clone)]
+pub struct PublicTag {
+    id: i64,
+    tag_name: String,
+    /// The full path of the tag, including the names of its ancestors.
+    path: String,
+    parent_tag_id: Option<i64>,
+    color: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct TagRecord {
+    id: i64,
+    #[allow(dead_code)]
+    account_id: Option<i32>,
+    tag_name: String,
+    path: String,
+    parent_tag_id: Option<i64>,
+    color: Option<String>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl From<TagRecord> for PublicTag {
+    fn from(record: TagRecord) -> Self {
+        PublicTag {
+            id: record.id,
+            tag_name: record.tag_name,
+            path: record.path,
+            parent_tag_id: record.parent_tag_id,
+            color: record.color,
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+fn validate_tag_name(tag_name: &str) -> APIResult<()> {
+    if tag_name.trim().is_empty() {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "Tag name must not be empty.",
+        ));
+    }
+
+    if tag_name.contains(TAG_PATH_SEPARATOR) {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "Tag name must not contain '/'.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Splits a tag path such as `work/clients/acme` into the names of the tags
+/// along it.
+fn split_tag_path(path: &str) -> APIResult<Vec<&str>> {
+    let tag_names = path
+        .split(TAG_PATH_SEPARATOR)
+        .map(str::trim)
+        .collect::<Vec<_>>();
+
+    for tag_name in &tag_names {
+        validate_tag_name(tag_name)?;
+    }
+
+    Ok(tag_names)
+}
+
+/// Colours are stored as `#RRGGBB` hex strings.
+fn validate_color(color: &str) -> APIResult<()> {
+    let valid = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+
+    if !valid {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "Colour must be in the format #RRGGBB.",
+        ));
+    }
+
+    Ok(())
+}
+
+fn map_tag_write_error(err: sqlx::Error, message: &str) -> ErrorResponse {
+    if is_unique_violation(&err) {
+        ErrorResponse::from(StatusCode::CONFLICT, "Tag name is already used.")
+    } else {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+pub(super) async fn validate_account_has_tag(
+    pg_pool: &PgPool,
+    account_id: i32,
+    tag_id: i64,
+) -> APIResult<()> {
+    sqlx::query!(
+        "
+            SELECT id FROM tag
+            WHERE id = $1 AND account_id = $2
+        ",
+        tag_id,
+        account_id
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to validate tag.")
+    })?
+    .map(|_record| ())
+    .ok_or_else(|| ErrorResponse::from(StatusCode::NOT_FOUND, "Tag does not exist."))
+}
+
+/// Whether `tag_id` is `ancestor_tag_id` itself or one of its descendants.
+async fn is_in_subtree(pg_pool: &PgPool, ancestor_tag_id: i64, tag_id: i64) -> APIResult<bool> {
+    sqlx::query!(
+        "SELECT $2 IN (SELECT tag_subtree($1)) AS \"in_subtree!\"",
+        ancestor_tag_id,
+        tag_id
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map(|record| record.in_subtree)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate tag hierarchy.",
+        )
+    })
+}
+
+/// Fetches the tags attached to each of the given todos.
+pub(super) async fn fetch_tags_by_todo_id(
+    pg_pool: &PgPool,
+    todo_ids: &[i64],
+) -> APIResult<HashMap<i64, Vec<PublicTag>>> {
+    let mut tags_by_todo_id = HashMap::<i64, Vec<PublicTag>>::new();
+
+    let records = sqlx::query!(
+        "
+            SELECT todo_tag.todo_id, tag.*, tag_path(tag.id) AS \"path!\" FROM todo_tag
+            JOIN tag ON tag.id = todo_tag.tag_id
+            WHERE todo_tag.todo_id = ANY($1)
+            ORDER BY tag_path(tag.id)
+        ",
+        todo_ids
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch tags.")
+    })?;
+
+    for record in records {
+        tags_by_todo_id
+            .entry(record.todo_id)
+            .or_default()
+            .push(PublicTag::from(TagRecord {
+                id: record.id,
+                account_id: record.account_id,
+                tag_name: record.tag_name,
+                path: record.path,
+                parent_tag_id: record.parent_tag_id,
+                color: record.color,
+                created_at: record.created_at,
+                updated_at: record.updated_at,
+            }));
+    }
+
+    Ok(tags_by_todo_id)
+}
+
+async fn fetch_tag(pg_pool: &PgPool, account_id: i32, tag_id: i64) -> APIResult<TagRecord> {
+    sqlx::query_as!(
+        TagRecord,
+        "
+            SELECT *, tag_path(id) AS \"path!\" FROM tag
+            WHERE id = $1 AND account_id = $2
+        ",
+        tag_id,
+        account_id
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .map_err(|_err| ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch tag."))?
+    .ok_or_else(|| ErrorResponse::from(StatusCode::NOT_FOUND, "Tag does not exist."))
+}
+
+pub async fn get_tags(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+) -> APIResponse<Vec<PublicTag>> {
+    Ok(sqlx::query_as!(
+        TagRecord,
+        "
+            SELECT *, tag_path(id) AS \"path!\" FROM tag
+            WHERE account_id = $1
+            ORDER BY tag_path(id)
+        ",
+        account_id
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch tags.")
+    })?
+    .into_iter()
+    .map(PublicTag::from)
+    .collect::<Vec<_>>()
+    .into())
+}
+
+/// Creates a tag. The name may be a path such as `work/clients/acme`, in which
+/// case missing ancestors are created as well.
+#[derive(Deserialize)]
+pub struct CreateTagRequest {
+    tag_name: String,
+    parent_tag_id: Option<i64>,
+    color: Option<String>,
+}
+
+/// Partial update of a tag. Missing fields are left unchanged, while fields
+/// explicitly set to `null` are cleared.
+#[derive(Deserialize)]
+pub struct UpdateTagRequest {
+    tag_name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    parent_tag_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    color: Option<Option<String>>,
+}
+
+/// Returns the ID of the tag with the given name under the given parent,
+/// creating it if it does not exist.
+async fn get_or_create_tag(
+    transaction: &mut Transaction<'_, Postgres>,
+    account_id: i32,
+    parent_tag_id: Option<i64>,
+    tag_name: &str,
+) -> APIResult<i64> {
+    sqlx::query!(
+        "
+            INSERT INTO tag (account_id, parent_tag_id, tag_name)
+            VALUES ($1, $2, $3)
+            ON CONFLICT ON CONSTRAINT tag_account_id_parent_tag_id_tag_name_unique DO NOTHING
+        ",
+        account_id,
+        parent_tag_id,
+        tag_name
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create tag.")
+    })?;
+
+    sqlx::query!(
+        "
+            SELECT id FROM tag
+            WHERE account_id = $1 AND parent_tag_id IS NOT DISTINCT FROM $2 AND tag_name = $3
+        ",
+        account_id,
+        parent_tag_id,
+        tag_name
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map(|record| record.id)
+    .map_err(|_err| ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create tag."))
+}
+
+pub async fn post_tags(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Json(req): Json<CreateTagRequest>,
+) -> APIResponse<PublicTag> {
+    let (tag_name, ancestor_names) = split_tag_path(&req.tag_name)?
+        .split_last()
+        .map(|(tag_name, ancestor_names)| (tag_name.to_string(), ancestor_names.to_vec()))
+        .expect("a tag path contains at least one name");
+    if let Some(parent_tag_id) = req.parent_tag_id {
+        validate_account_has_tag(&pg_pool, account_id, parent_tag_id).await?;
+    }
+    if let Some(color) = &req.color {
+        validate_color(color)?;
+    }
+
+    let mut transaction = pg_pool.begin().await.map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create tag.")
+    })?;
+
+    let mut parent_tag_id = req.parent_tag_id;
+    for ancestor_name in ancestor_names {
+        parent_tag_id = Some(
+            get_or_create_tag(&mut transaction, account_id, parent_tag_id, ancestor_name).await?,
+        );
+    }
+
+    let tag_id = sqlx::query!(
+        "
+            INSERT INTO tag (account_id, parent_tag_id, tag_name, color)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+        ",
+        account_id,
+        parent_tag_id,
+        tag_name,
+        req.color
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .map_err(|err| map_tag_write_error(err, "Failed to create tag."))?
+    .id;
+
+    transaction.commit().await.map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create tag.")
+    })?;
+
+    Ok(PublicTag::from(fetch_tag(&pg_pool, account_id, tag_id).await?).into())
+}
+
+pub async fn get_tag(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(tag_id): Path<i64>,
+) -> APIResponse<PublicTag> {
+    Ok(PublicTag::from(fetch_tag(&pg_pool, account_id, tag_id).await?).into())
+}
+
+pub async fn post_tag(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(tag_id): Path<i64>,
+    Json(req): Json<UpdateTagRequest>,
+) -> APIResponse<PublicTag> {
+    validate_account_has_tag(&pg_pool, account_id, tag_id).await?;
+    if let Some(tag_name) = &req.tag_name {
+        validate_tag_name(tag_name)?;
+    }
+    if let Some(Some(parent_tag_id)) = req.parent_tag_id {
+        validate_account_has_tag(&pg_pool, account_id, parent_tag_id).await?;
+        if is_in_subtree(&pg_pool, tag_id, parent_tag_id).await? {
+            return Err(ErrorResponse::from(
+                StatusCode::BAD_REQUEST,
+                "A tag cannot be nested under itself or its descendants.",
+            ));
+        }
+    }
+    if let Some(Some(color)) = &req.color {
+        validate_color(color)?;
+    }
+
+    sqlx::query!(
+        "
+            UPDATE tag
+            SET tag_name = COALESCE($2, tag_name),
+                parent_tag_id = CASE WHEN $3 THEN $4 ELSE parent_tag_id END,
+                color = CASE WHEN $5 THEN $6 ELSE color END
+            WHERE id = $1
+        ",
+        tag_id,
+        req.tag_name.as_deref().map(str::trim),
+        req.parent_tag_id.is_some(),
+        req.parent_tag_id.flatten(),
+        req.color.is_some(),
+        req.color.flatten()
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|err| map_tag_write_error(err, "Failed to update tag."))?;
+
+    Ok(PublicTag::from(fetch_tag(&pg_pool, account_id, tag_id).await?).into())
+}
+
+pub async fn delete_tag(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(tag_id): Path<i64>,
+) -> APIResponse<()> {
+    validate_account_has_tag(&pg_pool, account_id, tag_id).await?;
+
+    let mut transaction = pg_pool.begin().await.map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete tag.")
+    })?;
+
+    // Removing the tag, together with its descendants, changes the todos they
+    // were attached to.
+    sqlx::query!(
+        "
+            UPDATE todo
+            SET updated_at = CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+            WHERE id IN (
+                SELECT todo_id FROM todo_tag
+                WHERE tag_id IN (SELECT tag_subtree($1))
+            )
+        ",
+        tag_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete tag.")
+    })?;
+
+    sqlx::query!("DELETE FROM tag WHERE id = $1", tag_id)
+        .execute(&mut transaction)
+        .await
+        .map_err(|_err| {
+            ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete tag.")
+        })?;
+
+    transaction.commit().await.map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete tag.")
+    })?;
+
+    Ok(().into())
+}
+
+pub async fn post_todo_tag(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((todo_id, tag_id)): Path<(i64, i64)>,
+) -> APIResponse<()> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+    validate_account_has_tag(&pg_pool, account_id, tag_id).await?;
+
+    let inserted = sqlx::query!(
+        "
+            INSERT INTO todo_tag (todo_id, tag_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+        ",
+        todo_id,
+        tag_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to attach tag.")
+    })?
+    .rows_affected();
+
+    if inserted > 0 {
+        touch_todo(&pg_pool, todo_id).await?;
+    }
+
+    Ok(().into())
+}
+
+pub async fn delete_todo_tag(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((todo_id, tag_id)): Path<(i64, i64)>,
+) -> APIResponse<()> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let deleted = sqlx::query!(
+        "
+            DELETE FROM todo_tag
+            WHERE todo_id = $1 AND tag_id = $2
+        ",
+        todo_id,
+        tag_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to detach tag.")
+    })?
+    .rows_affected();
+
+    if deleted > 0 {
+        touch_todo(&pg_pool, todo_id).await?;
+    }
+
+    Ok(().into())
+}
+
+#[derive(Deserialize)]
+pub struct MergeTagRequest {
+    into_tag_id: i64,
+}
+
+/// Merges a tag into another one. Todos tagged with the merged tag are tagged
+/// with the target tag instead, and nested tags are moved under the target
+/// tag, merging them with existing nested tags of the same name. The merged
+/// tag is then deleted.
+pub async fn post_tag_merge(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(tag_id): Path<i64>,
+    Json(req): Json<MergeTagRequest>,
+) -> APIResponse<PublicTag> {
+    validate_account_has_tag(&pg_pool, account_id, tag_id).await?;
+    validate_account_has_tag(&pg_pool, account_id, req.into_tag_id).await?;
+    if is_in_subtree(&pg_pool, tag_id, req.into_tag_id).await? {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "A tag cannot be merged into itself or its descendants.",
+        ));
+    }
+
+    let mut transaction = pg_pool.begin().await.map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to merge tags.")
+    })?;
+
+    let mut merged_tag_ids = Vec::new();
+    let mut pending_merges = vec![(tag_id, req.into_tag_id)];
+    while let Some((source_tag_id, target_tag_id)) = pending_merges.pop() {
+        merged_tag_ids.push(source_tag_id);
+
+        sqlx::query!(
+            "
+                UPDATE todo
+                SET updated_at = CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+                WHERE id IN (SELECT todo_id FROM todo_tag WHERE tag_id = $1)
+            ",
+            source_tag_id
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|_err| {
+            ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to merge tags.")
+        })?;
+
+        sqlx::query!(
+            "
+                INSERT INTO todo_tag (todo_id, tag_id)
+                SELECT todo_id, $2 FROM todo_tag
+                WHERE tag_id = $1
+                ON CONFLICT DO NOTHING
+            ",
+            source_tag_id,
+            target_tag_id
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|_err| {
+            ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to merge tags.")
+        })?;
+
+        sqlx::query!(
+            "
+                INSERT INTO todo_template_tag (template_id, tag_id)
+                SELECT template_id, $2 FROM todo_template_tag
+                WHERE tag_id = $1
+                ON CONFLICT DO NOTHING
+            ",
+            source_tag_id,
+            target_tag_id
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|_err| {
+            ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to merge tags.")
+        })?;
+
+        let children = sqlx::query!(
+            "
+                SELECT child.id, existing.id AS \"existing_id?\" FROM tag child
+                LEFT JOIN tag existing
+                    ON existing.parent_tag_id = $2 AND existing.tag_name = child.tag_name
+                WHERE child.parent_tag_id = $1
+            ",
+            source_tag_id,
+            target_tag_id
+        )
+        .fetch_all(&mut transaction)
+        .await
+        .map_err(|_err| {
+            ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to merge tags.")
+        })?;
+
+        for child in children {
+            match child.existing_id {
+                Some(existing_id) => pending_merges.push((child.id, existing_id)),
+                None => {
+                    sqlx::query!(
+                        "UPDATE tag SET parent_tag_id = $2 WHERE id = $1",
+                        child.id,
+                        target_tag_id
+                    )
+                    .execute(&mut transaction)
+                    .await
+                    .map_err(|_err| {
+                        ErrorResponse::from(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Failed to merge tags.",
+                        )
+                    })?;
+                }
+            }
+        }
+    }
+
+    sqlx::query!("DELETE FROM tag WHERE id = ANY($1)", &merged_tag_ids)
+        .execute(&mut transaction)
+        .await
+        .map_err(|_err| {
+            ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to merge tags.")
+        })?;
+
+    transaction.commit().await.map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to merge tags.")
+    })?;
+
+    Ok(PublicTag::from(fetch_tag(&pg_pool, account_id, req.into_tag_id).await?).into())
+}
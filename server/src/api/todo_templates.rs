@@ -0,0 +1,567 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+
+use crate::auth::AccountId;
+
+use super::{
+    deserialize_optional_field, is_unique_violation,
+    ordering::RANK_GAP,
+    tags::validate_account_has_tag,
+    todos::{
+        fetch_todo, todo_response, validate_account_can_edit_project, validate_account_has_project,
+        validate_priority, PublicTodo,
+    },
+    APIResponse, APIResult, ErrorResponse,
+};
+
+#[derive(Serialize)]
+pub struct PublicTodoTemplate {
+    id: i64,
+    template_name: String,
+    /// The title of created todos. May contain placeholders.
+    title: String,
+    /// The memo of created todos. May contain placeholders.
+    memo: String,
+    priority: i16,
+    /// The project created todos are added to, unless another project is
+    /// given.
+    project_id: Option<i64>,
+    /// Created todos are due this many days after the date they are created
+    /// for.
+    deadline_in_days: Option<i32>,
+    /// The texts of the checklist items of created todos.
+    checklist: Vec<String>,
+    /// The tags attached to created todos.
+    tag_ids: Vec<i64>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct TodoTemplateRecord {
+    id: i64,
+    template_name: String,
+    title: String,
+    memo: String,
+    priority: i16,
+    project_id: Option<i64>,
+    deadline_in_days: Option<i32>,
+    checklist: Vec<String>,
+    tag_ids: Vec<i64>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl From<TodoTemplateRecord> for PublicTodoTemplate {
+    fn from(record: TodoTemplateRecord) -> Self {
+        PublicTodoTemplate {
+            id: record.id,
+            template_name: record.template_name,
+            title: record.title,
+            memo: record.memo,
+            priority: record.priority,
+            project_id: record.project_id,
+            deadline_in_days: record.deadline_in_days,
+            checklist: record.checklist,
+            tag_ids: record.tag_ids,
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+/// Fills in the placeholders of a template text for the given date:
+/// `{{date}}` (`2022-11-02`), `{{week}}` (`2022-W44`), `{{month}}`
+/// (`2022-11`), and `{{year}}` (`2022`). Unknown placeholders are kept.
+fn fill_placeholders(text: &str, date: NaiveDate) -> String {
+    let week = date.iso_week();
+
+    text.replace("{{date}}", &date.format("%Y-%m-%d").to_string())
+        .replace("{{week}}", &format!("{}-W{:02}", week.year(), week.week()))
+        .replace("{{month}}", &date.format("%Y-%m").to_string())
+        .replace("{{year}}", &date.year().to_string())
+}
+
+async fn fetch_todo_templates(
+    pg_pool: &PgPool,
+    account_id: i32,
+    template_id: Option<i64>,
+) -> APIResult<Vec<TodoTemplateRecord>> {
+    sqlx::query_as!(
+        TodoTemplateRecord,
+        "
+            SELECT
+                id,
+                template_name,
+                title,
+                memo,
+                priority,
+                project_id,
+                deadline_in_days,
+                checklist,
+                ARRAY(
+                    SELECT tag_id FROM todo_template_tag
+                    WHERE template_id = todo_template.id
+                    ORDER BY tag_id
+                ) AS \"tag_ids!\",
+                created_at,
+                updated_at
+            FROM todo_template
+            WHERE account_id = $1 AND ($2::BIGINT IS NULL OR id = $2)
+            ORDER BY template_name, id
+        ",
+        account_id,
+        template_id
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch todo templates.",
+        )
+    })
+}
+
+async fn fetch_todo_template(
+    pg_pool: &PgPool,
+    account_id: i32,
+    template_id: i64,
+) -> APIResult<TodoTemplateRecord> {
+    fetch_todo_templates(pg_pool, account_id, Some(template_id))
+        .await?
+        .pop()
+        .ok_or_else(|| {
+            ErrorResponse::from(StatusCode::NOT_FOUND, "The todo template does not exist.")
+        })
+}
+
+pub async fn get_todo_templates(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+) -> APIResponse<Vec<PublicTodoTemplate>> {
+    Ok(fetch_todo_templates(&pg_pool, account_id, None)
+        .await?
+        .into_iter()
+        .map(PublicTodoTemplate::from)
+        .collect::<Vec<_>>()
+        .into())
+}
+
+pub async fn get_todo_template(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(template_id): Path<i64>,
+) -> APIResponse<PublicTodoTemplate> {
+    Ok(
+        PublicTodoTemplate::from(fetch_todo_template(&pg_pool, account_id, template_id).await?)
+            .into(),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct CreateTodoTemplateRequest {
+    template_name: String,
+    title: String,
+    memo: Option<String>,
+    priority: Option<i16>,
+    project_id: Option<i64>,
+    deadline_in_days: Option<i32>,
+    #[serde(default)]
+    checklist: Vec<String>,
+    #[serde(default)]
+    tag_ids: Vec<i64>,
+}
+
+/// Partial update of a todo template. Missing fields are left unchanged,
+/// while fields explicitly set to `null` are cleared. The checklist and tags
+/// are replaced as a whole.
+#[derive(Deserialize)]
+pub struct UpdateTodoTemplateRequest {
+    template_name: Option<String>,
+    title: Option<String>,
+    memo: Option<String>,
+    priority: Option<i16>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    project_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    deadline_in_days: Option<Option<i32>>,
+    checklist: Option<Vec<String>>,
+    tag_ids: Option<Vec<i64>>,
+}
+
+/// Creates a todo from a template.
+#[derive(Deserialize)]
+pub struct InstantiateTodoTemplateRequest {
+    /// The date placeholders and the deadline are filled in for. Defaults to
+    /// today in the time zone of the user.
+    date: Option<NaiveDate>,
+    /// The project to add the todo to instead of the project of the template.
+    project_id: Option<i64>,
+}
+
+fn map_todo_template_write_error(err: sqlx::Error, message: &'static str) -> ErrorResponse {
+    if is_unique_violation(&err) {
+        ErrorResponse::from(
+            StatusCode::CONFLICT,
+            "A todo template with the same name already exists.",
+        )
+    } else {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+/// The maximum number of days between the date a todo is created for and its
+/// deadline.
+const MAX_DEADLINE_IN_DAYS: i32 = 3650;
+
+fn validate_deadline_in_days(deadline_in_days: i32) -> APIResult<()> {
+    if deadline_in_days < 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The deadline cannot be before the date the todo is created for.",
+        ));
+    }
+    if deadline_in_days > MAX_DEADLINE_IN_DAYS {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The deadline can be at most 3650 days after the date the todo is created for.",
+        ));
+    }
+
+    Ok(())
+}
+
+async fn validate_account_has_tags(
+    pg_pool: &PgPool,
+    account_id: i32,
+    tag_ids: &[i64],
+) -> APIResult<()> {
+    for tag_id in tag_ids {
+        validate_account_has_tag(pg_pool, account_id, *tag_id).await?;
+    }
+
+    Ok(())
+}
+
+async fn replace_todo_template_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    template_id: i64,
+    tag_ids: &[i64],
+    message: &'static str,
+) -> APIResult<()> {
+    sqlx::query!(
+        "DELETE FROM todo_template_tag WHERE template_id = $1",
+        template_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|_err| ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, message))?;
+
+    sqlx::query!(
+        "
+            INSERT INTO todo_template_tag (template_id, tag_id)
+            SELECT $1, tag_id FROM UNNEST($2::BIGINT[]) AS tag_id
+            ON CONFLICT DO NOTHING
+        ",
+        template_id,
+        tag_ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|_err| ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, message))?;
+
+    Ok(())
+}
+
+pub async fn post_todo_templates(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Json(req): Json<CreateTodoTemplateRequest>,
+) -> APIResponse<PublicTodoTemplate> {
+    if let Some(priority) = req.priority {
+        validate_priority(priority)?;
+    }
+    if let Some(project_id) = req.project_id {
+        validate_account_has_project(&pg_pool, account_id, project_id).await?;
+    }
+    if let Some(deadline_in_days) = req.deadline_in_days {
+        validate_deadline_in_days(deadline_in_days)?;
+    }
+    validate_account_has_tags(&pg_pool, account_id, &req.tag_ids).await?;
+
+    let mut transaction = pg_pool.begin().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create todo template.",
+        )
+    })?;
+
+    let template_id = sqlx::query!(
+        "
+            INSERT INTO todo_template (
+                account_id, template_name, title, memo, priority, project_id, deadline_in_days,
+                checklist
+            )
+            VALUES ($1, $2, $3, $4, COALESCE($5::SMALLINT, 0), $6, $7, $8)
+            RETURNING id
+        ",
+        account_id,
+        req.template_name.trim(),
+        &req.title,
+        req.memo.unwrap_or_default(),
+        req.priority,
+        req.project_id,
+        req.deadline_in_days,
+        &req.checklist
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .map_err(|err| map_todo_template_write_error(err, "Failed to create todo template."))?
+    .id;
+
+    replace_todo_template_tags(
+        &mut transaction,
+        template_id,
+        &req.tag_ids,
+        "Failed to create todo template.",
+    )
+    .await?;
+
+    transaction.commit().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to create todo template.",
+        )
+    })?;
+
+    Ok(
+        PublicTodoTemplate::from(fetch_todo_template(&pg_pool, account_id, template_id).await?)
+            .into(),
+    )
+}
+
+pub async fn post_todo_template(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(template_id): Path<i64>,
+    Json(req): Json<UpdateTodoTemplateRequest>,
+) -> APIResponse<PublicTodoTemplate> {
+    fetch_todo_template(&pg_pool, account_id, template_id).await?;
+    if let Some(priority) = req.priority {
+        validate_priority(priority)?;
+    }
+    if let Some(Some(project_id)) = req.project_id {
+        validate_account_has_project(&pg_pool, account_id, project_id).await?;
+    }
+    if let Some(Some(deadline_in_days)) = req.deadline_in_days {
+        validate_deadline_in_days(deadline_in_days)?;
+    }
+    if let Some(tag_ids) = &req.tag_ids {
+        validate_account_has_tags(&pg_pool, account_id, tag_ids).await?;
+    }
+
+    let mut transaction = pg_pool.begin().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update todo template.",
+        )
+    })?;
+
+    sqlx::query!(
+        "
+            UPDATE todo_template
+            SET template_name = COALESCE($2, template_name),
+                title = COALESCE($3, title),
+                memo = COALESCE($4, memo),
+                priority = COALESCE($5, priority),
+                project_id = CASE WHEN $6 THEN $7 ELSE project_id END,
+                deadline_in_days = CASE WHEN $8 THEN $9 ELSE deadline_in_days END,
+                checklist = COALESCE($10, checklist)
+            WHERE id = $1
+        ",
+        template_id,
+        req.template_name.as_deref().map(str::trim),
+        req.title,
+        req.memo,
+        req.priority,
+        req.project_id.is_some(),
+        req.project_id.flatten(),
+        req.deadline_in_days.is_some(),
+        req.deadline_in_days.flatten(),
+        req.checklist.as_deref()
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|err| map_todo_template_write_error(err, "Failed to update todo template."))?;
+
+    if let Some(tag_ids) = &req.tag_ids {
+        replace_todo_template_tags(
+            &mut transaction,
+            template_id,
+            tag_ids,
+            "Failed to update todo template.",
+        )
+        .await?;
+    }
+
+    transaction.commit().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update todo template.",
+        )
+    })?;
+
+    Ok(
+        PublicTodoTemplate::from(fetch_todo_template(&pg_pool, account_id, template_id).await?)
+            .into(),
+    )
+}
+
+pub async fn delete_todo_template(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(template_id): Path<i64>,
+) -> APIResponse<()> {
+    let deleted = sqlx::query!(
+        "
+            DELETE FROM todo_template
+            WHERE id = $1 AND account_id = $2
+        ",
+        template_id,
+        account_id
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete todo template.",
+        )
+    })?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The todo template does not exist.",
+        ));
+    }
+
+    Ok(().into())
+}
+
+fn map_instantiate_error(_err: sqlx::Error) -> ErrorResponse {
+    ErrorResponse::from(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to create todo from template.",
+    )
+}
+
+/// Creates a todo from a template, with its placeholders filled in, its tags
+/// attached, and its checklist unchecked. A todo created in a project gets
+/// the next number of the project.
+pub async fn post_todo_template_instantiate(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(template_id): Path<i64>,
+    Json(req): Json<InstantiateTodoTemplateRequest>,
+) -> APIResponse<PublicTodo> {
+    let template = fetch_todo_template(&pg_pool, account_id, template_id).await?;
+    let project_id = req.project_id.or(template.project_id);
+    if let Some(project_id) = project_id {
+        validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+    }
+
+    let date = match req.date {
+        Some(date) => date,
+        None => {
+            sqlx::query!(
+                "SELECT local_today(time_zone) AS \"today!\" FROM account WHERE id = $1",
+                account_id
+            )
+            .fetch_one(&pg_pool)
+            .await
+            .map_err(map_instantiate_error)?
+            .today
+        }
+    };
+    let deadline_date = template
+        .deadline_in_days
+        .map(|days| {
+            date.checked_add_signed(chrono::Duration::days(days.into()))
+                .ok_or_else(|| {
+                    ErrorResponse::from(StatusCode::BAD_REQUEST, "The deadline is out of range.")
+                })
+        })
+        .transpose()?;
+
+    let mut transaction = pg_pool.begin().await.map_err(map_instantiate_error)?;
+
+    let todo_id = sqlx::query!(
+        "
+            INSERT INTO todo (
+                account_id, title, memo, deadline_date, project_id, project_todo_number, priority,
+                sort_rank
+            )
+            VALUES (
+                $1, $2, $3, $4, $5::BIGINT,
+                CASE WHEN $5 IS NULL THEN NULL ELSE allocate_project_todo_number($5) END,
+                $6,
+                (
+                    SELECT COALESCE(MAX(sort_rank), 0) + $7 FROM todo
+                    WHERE account_id = $1 AND project_id IS NOT DISTINCT FROM $5
+                )
+            )
+            RETURNING id
+        ",
+        account_id,
+        fill_placeholders(&template.title, date),
+        fill_placeholders(&template.memo, date),
+        deadline_date,
+        project_id,
+        template.priority,
+        RANK_GAP
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .map_err(map_instantiate_error)?
+    .id;
+
+    sqlx::query!(
+        "
+            INSERT INTO todo_tag (todo_id, tag_id)
+            SELECT $2, tag_id FROM todo_template_tag WHERE template_id = $1
+        ",
+        template_id,
+        todo_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_instantiate_error)?;
+
+    sqlx::query!(
+        "
+            INSERT INTO checklist_item (todo_id, position, text)
+            SELECT $1, item.position - 1, item.text
+            FROM UNNEST($2::TEXT[]) WITH ORDINALITY AS item(text, position)
+        ",
+        todo_id,
+        &template.checklist
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_instantiate_error)?;
+
+    transaction.commit().await.map_err(map_instantiate_error)?;
+
+    let record = fetch_todo(&pg_pool, account_id, todo_id).await?;
+
+    todo_response(&pg_pool, record).await
+}
@@ -1,65 +1,745 @@
+use std::collections::HashMap;
+
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     http::StatusCode,
-    response::IntoResponse,
     Json,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
 
-use crate::auth::AccountId;
+use crate::{auth::AccountId, etag::IfMatch};
 
-use super::{APIResponse, APIResult, ErrorResponse};
+use super::{
+    checklists::fetch_checklist_counts_by_todo_id,
+    dependencies::fetch_blockers_by_todo_id,
+    deserialize_optional_field,
+    iterations::validate_iteration,
+    milestones::validate_milestone,
+    ordering::RANK_GAP,
+    recurrence::{create_next_occurrence, Recurrence},
+    reminders::reschedule_deadline_reminders,
+    sections::validate_section,
+    tags::fetch_tags_by_todo_id,
+    tags::PublicTag,
+    workflow_states::{validate_workflow_state, StateCategory},
+    APIResponse, APIResult, ErrorResponse, SuccessResponse,
+};
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct PublicTodo {
     id: i64,
     title: String,
     memo: String,
     completed_at: Option<DateTime<Utc>>,
     deadline: Option<DateTime<Utc>>,
+    /// A deadline without a time of day, due by the end of the day. Never set
+    /// together with `deadline`.
+    deadline_date: Option<NaiveDate>,
+    /// The todo is deferred, and hidden from available todos, before this date.
+    start_date: Option<NaiveDate>,
+    /// The date the todo is planned to be worked on.
+    scheduled_date: Option<NaiveDate>,
     project_id: Option<i64>,
     project_todo_number: Option<i32>,
+    /// The current project reference of the todo, such as `ABC-12`.
+    reference: Option<String>,
+    tags: Vec<PublicTag>,
+    parent_todo_id: Option<i64>,
+    /// From 0 (no priority) to 4 (highest priority).
+    priority: i16,
+    /// The manual position of the todo within its project, or within the
+    /// inbox for todos without a project.
+    sort_rank: i64,
+    /// An RFC 5545 recurrence rule, such as `FREQ=WEEKLY;BYDAY=MO,TH`.
+    recurrence: Option<String>,
+    /// Whether the next occurrence is scheduled relative to the completion
+    /// of the todo instead of following the rule.
+    recurs_from_completion: bool,
+    /// The occurrence this todo was created from when it was completed.
+    previous_occurrence_todo_id: Option<i64>,
+    /// The todo is hidden from the todo list until this time.
+    snoozed_until: Option<DateTime<Utc>>,
+    /// How often the todo was snoozed.
+    snooze_count: i32,
+    /// The workflow state of the todo within its project. Todos are completed
+    /// exactly when their state is in the closed category.
+    workflow_state_id: Option<i64>,
+    /// The section of the project the todo is listed under.
+    section_id: Option<i64>,
+    /// The milestone of the project the todo counts towards.
+    milestone_id: Option<i64>,
+    /// The iteration of the project the todo is planned in.
+    iteration_id: Option<i64>,
+    /// The number of subtasks of the todo, at any depth.
+    subtask_count: i64,
+    completed_subtask_count: i64,
+    /// The percentage of subtasks that are completed, or `None` if the todo
+    /// has no subtasks.
+    progress: Option<f64>,
+    checklist_item_count: i64,
+    checked_checklist_item_count: i64,
+    /// The todos that have to be completed before this todo.
+    blocked_by: Vec<i64>,
+    /// Whether any of the todos in `blocked_by` is not completed yet.
+    blocked: bool,
+    /// The subtasks of the todo, only included when fetching a single todo.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subtasks: Option<Vec<PublicTodo>>,
+    version: i32,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
 }
 
-pub async fn get_todos(
-    AccountId(account_id): AccountId,
-    Extension(pg_pool): Extension<PgPool>,
-) -> impl IntoResponse {
-    let todos = sqlx::query!("SELECT * FROM todo WHERE account_id = $1", account_id)
-        .fetch_all(&pg_pool)
-        .await
-        .unwrap()
-        .into_iter()
-        .map(|record| PublicTodo {
+pub(super) struct TodoRecord {
+    id: i64,
+    #[allow(dead_code)]
+    account_id: i32,
+    title: String,
+    memo: String,
+    completed_at: Option<NaiveDateTime>,
+    deadline: Option<NaiveDateTime>,
+    project_id: Option<i64>,
+    project_todo_number: Option<i32>,
+    version: i32,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    parent_todo_id: Option<i64>,
+    priority: i16,
+    sort_rank: i64,
+    deadline_date: Option<NaiveDate>,
+    start_date: Option<NaiveDate>,
+    scheduled_date: Option<NaiveDate>,
+    recurrence: Option<String>,
+    recurs_from_completion: bool,
+    previous_occurrence_todo_id: Option<i64>,
+    snoozed_until: Option<NaiveDateTime>,
+    snooze_count: i32,
+    workflow_state_id: Option<i64>,
+    section_id: Option<i64>,
+    milestone_id: Option<i64>,
+    iteration_id: Option<i64>,
+}
+
+impl From<TodoRecord> for PublicTodo {
+    fn from(record: TodoRecord) -> Self {
+        PublicTodo {
             id: record.id,
             title: record.title,
             memo: record.memo,
+            deadline_date: record.deadline_date,
+            start_date: record.start_date,
+            scheduled_date: record.scheduled_date,
             project_id: record.project_id,
             project_todo_number: record.project_todo_number,
+            reference: None,
+            tags: Vec::new(),
+            parent_todo_id: record.parent_todo_id,
+            priority: record.priority,
+            sort_rank: record.sort_rank,
+            recurrence: record.recurrence,
+            recurs_from_completion: record.recurs_from_completion,
+            previous_occurrence_todo_id: record.previous_occurrence_todo_id,
+            snoozed_until: record
+                .snoozed_until
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+            snooze_count: record.snooze_count,
+            workflow_state_id: record.workflow_state_id,
+            section_id: record.section_id,
+            milestone_id: record.milestone_id,
+            iteration_id: record.iteration_id,
+            subtask_count: 0,
+            completed_subtask_count: 0,
+            progress: None,
+            checklist_item_count: 0,
+            checked_checklist_item_count: 0,
+            blocked_by: Vec::new(),
+            blocked: false,
+            subtasks: None,
+            version: record.version,
             completed_at: record
                 .completed_at
-                .map(|datetime| DateTime::<Utc>::from_utc(datetime, Utc)),
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
             deadline: record
                 .deadline
-                .map(|datetime| DateTime::<Utc>::from_utc(datetime, Utc)),
-        })
+                .map(|datetime| DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc)),
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+fn format_reference(shortcode: &str, project_todo_number: i32) -> String {
+    format!("{}-{}", shortcode, project_todo_number)
+}
+
+fn parse_reference(reference: &str) -> Option<(&str, i32)> {
+    let (shortcode, project_todo_number) = reference.rsplit_once('-')?;
+    Some((shortcode, project_todo_number.parse().ok()?))
+}
+
+/// Converts todo records into their public representation, loading the data
+/// that is not stored on the `todo` row itself.
+async fn to_public_todos(pg_pool: &PgPool, records: Vec<TodoRecord>) -> APIResult<Vec<PublicTodo>> {
+    let project_ids = records
+        .iter()
+        .filter_map(|record| record.project_id)
         .collect::<Vec<_>>();
 
-    Json(todos)
+    let shortcodes = sqlx::query!(
+        "
+            SELECT id, shortcode FROM project
+            WHERE id = ANY($1)
+        ",
+        &project_ids
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch projects.",
+        )
+    })?
+    .into_iter()
+    .map(|project| (project.id, project.shortcode))
+    .collect::<HashMap<_, _>>();
+
+    let todo_ids = records.iter().map(|record| record.id).collect::<Vec<_>>();
+    let mut tags_by_todo_id = fetch_tags_by_todo_id(pg_pool, &todo_ids).await?;
+    let checklist_counts = fetch_checklist_counts_by_todo_id(pg_pool, &todo_ids).await?;
+    let mut blockers_by_todo_id = fetch_blockers_by_todo_id(pg_pool, &todo_ids).await?;
+
+    let subtask_counts = sqlx::query!(
+        "
+            SELECT
+                root_id AS \"todo_id!\",
+                COUNT(*) AS \"subtask_count!\",
+                COUNT(todo.completed_at) AS \"completed_subtask_count!\"
+            FROM UNNEST($1::BIGINT[]) AS root_id
+            CROSS JOIN LATERAL todo_subtree(root_id) AS subtask_id
+            JOIN todo ON todo.id = subtask_id
+            WHERE subtask_id <> root_id
+            GROUP BY root_id
+        ",
+        &todo_ids
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch subtasks.",
+        )
+    })?
+    .into_iter()
+    .map(|record| {
+        (
+            record.todo_id,
+            (record.subtask_count, record.completed_subtask_count),
+        )
+    })
+    .collect::<HashMap<_, _>>();
+
+    Ok(records
+        .into_iter()
+        .map(|record| {
+            let reference = record
+                .project_id
+                .and_then(|project_id| shortcodes.get(&project_id))
+                .zip(record.project_todo_number)
+                .map(|(shortcode, project_todo_number)| {
+                    format_reference(shortcode, project_todo_number)
+                });
+
+            let (subtask_count, completed_subtask_count) =
+                subtask_counts.get(&record.id).copied().unwrap_or((0, 0));
+            let progress = (subtask_count > 0)
+                .then(|| completed_subtask_count as f64 / subtask_count as f64 * 100.0);
+            let checklist_counts = checklist_counts
+                .get(&record.id)
+                .copied()
+                .unwrap_or_default();
+            let blockers = blockers_by_todo_id.remove(&record.id).unwrap_or_default();
+
+            PublicTodo {
+                reference,
+                tags: tags_by_todo_id.remove(&record.id).unwrap_or_default(),
+                subtask_count,
+                completed_subtask_count,
+                progress,
+                checklist_item_count: checklist_counts.item_count,
+                checked_checklist_item_count: checklist_counts.checked_item_count,
+                blocked_by: blockers.blocked_by,
+                blocked: blockers.blocked,
+                ..PublicTodo::from(record)
+            }
+        })
+        .collect())
+}
+
+pub(super) async fn to_public_todo(pg_pool: &PgPool, record: TodoRecord) -> APIResult<PublicTodo> {
+    Ok(to_public_todos(pg_pool, vec![record])
+        .await?
+        .pop()
+        .expect("one todo is converted from one record"))
+}
+
+/// Fetches the todos of a project by id.
+pub(super) async fn fetch_project_todos(
+    pg_pool: &PgPool,
+    project_id: i64,
+) -> APIResult<HashMap<i64, PublicTodo>> {
+    let records = sqlx::query_as!(
+        TodoRecord,
+        "
+            SELECT * FROM todo
+            WHERE project_id = $1
+        ",
+        project_id
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch todos.")
+    })?;
+
+    Ok(to_public_todos(pg_pool, records)
+        .await?
+        .into_iter()
+        .map(|todo| (todo.id, todo))
+        .collect())
+}
+
+/// Responds with a single todo, tagged with its version.
+pub(super) async fn todo_response(pg_pool: &PgPool, record: TodoRecord) -> APIResponse<PublicTodo> {
+    let version = record.version;
+
+    Ok(SuccessResponse::from(to_public_todo(pg_pool, record).await?).with_etag(version))
+}
+
+fn attach_subtasks(
+    todo: &mut PublicTodo,
+    subtasks_by_parent_id: &mut HashMap<i64, Vec<PublicTodo>>,
+) {
+    let mut subtasks = subtasks_by_parent_id.remove(&todo.id).unwrap_or_default();
+    for subtask in &mut subtasks {
+        attach_subtasks(subtask, subtasks_by_parent_id);
+    }
+    todo.subtasks = Some(subtasks);
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoSort {
+    #[default]
+    UpdatedAt,
+    CreatedAt,
+    Deadline,
+    /// Highest priority first, then by manual position.
+    Priority,
+    /// Manual position within each list.
+    Position,
+}
+
+impl TodoSort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TodoSort::UpdatedAt => "updated_at",
+            TodoSort::CreatedAt => "created_at",
+            TodoSort::Deadline => "deadline",
+            TodoSort::Priority => "priority",
+            TodoSort::Position => "position",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GetTodosQuery {
+    /// Only return todos modified after this time, for incremental sync.
+    updated_since: Option<DateTime<Utc>>,
+    /// Only return todos with this tag, or one of its descendants, attached.
+    tag_id: Option<i64>,
+    /// Only return todos that are not blocked by incomplete todos.
+    #[serde(default)]
+    actionable: bool,
+    /// Only return todos due on or before this date, in the time zone of the
+    /// user.
+    due_by: Option<NaiveDate>,
+    /// Only return todos due today, in the time zone of the user.
+    #[serde(default)]
+    due_today: bool,
+    /// Only return incomplete todos whose deadline has passed.
+    #[serde(default)]
+    overdue: bool,
+    /// Only return todos that are not deferred past this date.
+    available_on: Option<NaiveDate>,
+    /// Only return todos scheduled on or before this date.
+    scheduled_by: Option<NaiveDate>,
+    /// Also return todos that are snoozed.
+    #[serde(default)]
+    include_snoozed: bool,
+    /// Only return todos in this workflow state.
+    workflow_state_id: Option<i64>,
+    /// Only return todos whose workflow state is in this category.
+    state_category: Option<StateCategory>,
+    /// Only return todos in this section.
+    section_id: Option<i64>,
+    /// Only return todos assigned to this milestone.
+    milestone_id: Option<i64>,
+    /// Only return todos planned in this iteration.
+    iteration_id: Option<i64>,
+    /// Also return todos of archived projects.
+    #[serde(default)]
+    include_archived: bool,
+    /// Also return todos of project templates.
+    #[serde(default)]
+    include_templates: bool,
+    #[serde(default)]
+    sort: TodoSort,
+}
+
+pub async fn get_todos(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Query(query): Query<GetTodosQuery>,
+) -> APIResult<Json<Vec<PublicTodo>>> {
+    let todos = sqlx::query_as!(
+        TodoRecord,
+        "
+            WITH local AS (
+                SELECT time_zone, local_today(time_zone) AS today FROM account WHERE id = $1
+            )
+            SELECT todo.* FROM todo, local
+            WHERE account_id = $1
+                AND ($2::TIMESTAMP IS NULL OR updated_at > $2)
+                AND ($3::BIGINT IS NULL OR EXISTS (
+                    SELECT 1 FROM todo_tag
+                    WHERE todo_tag.todo_id = todo.id
+                        AND todo_tag.tag_id IN (SELECT tag_subtree($3))
+                ))
+                AND (NOT $4 OR NOT EXISTS (
+                    SELECT 1 FROM todo_dependency
+                    JOIN todo blocker ON blocker.id = todo_dependency.blocked_by_todo_id
+                    WHERE todo_dependency.todo_id = todo.id AND blocker.completed_at IS NULL
+                ))
+                AND ($6::DATE IS NULL
+                    OR deadline < local_day_start($6 + 1, local.time_zone)
+                    OR deadline_date <= $6)
+                AND (NOT $9
+                    OR deadline >= local_day_start(local.today, local.time_zone)
+                        AND deadline < local_day_start(local.today + 1, local.time_zone)
+                    OR deadline_date = local.today)
+                AND (NOT $10 OR completed_at IS NULL AND (
+                    deadline < CURRENT_TIMESTAMP AT TIME ZONE 'UTC' OR deadline_date < local.today
+                ))
+                AND ($11 OR snoozed_until IS NULL
+                    OR snoozed_until <= CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+                AND ($7::DATE IS NULL OR start_date IS NULL OR start_date <= $7)
+                AND ($8::DATE IS NULL OR scheduled_date <= $8)
+                AND ($12::BIGINT IS NULL OR workflow_state_id = $12)
+                AND ($13::TEXT IS NULL OR workflow_state_id IN (
+                    SELECT id FROM workflow_state WHERE category = $13
+                ))
+                AND ($14::BIGINT IS NULL OR section_id = $14)
+                AND ($15::BIGINT IS NULL OR milestone_id = $15)
+                AND ($16::BIGINT IS NULL OR iteration_id = $16)
+                AND (project_id IS NULL OR project_id NOT IN (
+                    SELECT id FROM project
+                    WHERE account_id = $1
+                        AND (NOT $17 AND archived_at IS NOT NULL OR NOT $18 AND is_template)
+                ))
+            ORDER BY
+                CASE WHEN $5 = 'priority' THEN priority END DESC,
+                CASE WHEN $5 IN ('priority', 'position') THEN project_id END,
+                CASE WHEN $5 IN ('priority', 'position') THEN sort_rank END,
+                CASE WHEN $5 = 'deadline' THEN
+                    COALESCE(deadline, local_day_start(deadline_date + 1, local.time_zone)) END,
+                CASE WHEN $5 = 'created_at' THEN created_at END,
+                updated_at,
+                id
+        ",
+        account_id,
+        query.updated_since.map(|datetime| datetime.naive_utc()),
+        query.tag_id,
+        query.actionable,
+        query.sort.as_str(),
+        query.due_by,
+        query.available_on,
+        query.scheduled_by,
+        query.due_today,
+        query.overdue,
+        query.include_snoozed,
+        query.workflow_state_id,
+        query.state_category.map(|category| category.as_str()),
+        query.section_id,
+        query.milestone_id,
+        query.iteration_id,
+        query.include_archived,
+        query.include_templates
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .unwrap();
+
+    to_public_todos(&pg_pool, todos).await.map(Json)
+}
+
+pub async fn get_todo(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+) -> APIResponse<PublicTodo> {
+    let record = fetch_todo(&pg_pool, account_id, todo_id).await?;
+    let version = record.version;
+
+    let subtask_records = sqlx::query_as!(
+        TodoRecord,
+        "
+            SELECT * FROM todo
+            WHERE id IN (SELECT todo_subtree($1)) AND id <> $1
+            ORDER BY created_at
+        ",
+        todo_id
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch subtasks.",
+        )
+    })?;
+
+    let mut subtasks_by_parent_id = HashMap::<i64, Vec<PublicTodo>>::new();
+    for subtask in to_public_todos(&pg_pool, subtask_records).await? {
+        if let Some(parent_todo_id) = subtask.parent_todo_id {
+            subtasks_by_parent_id
+                .entry(parent_todo_id)
+                .or_default()
+                .push(subtask);
+        }
+    }
+
+    let mut todo = to_public_todo(&pg_pool, record).await?;
+    attach_subtasks(&mut todo, &mut subtasks_by_parent_id);
+
+    Ok(SuccessResponse::from(todo).with_etag(version))
+}
+
+/// Resolves a project reference such as `ABC-12` to a todo. References the
+/// todo held before moving to another project, and references using previous
+/// shortcodes of the project, keep resolving.
+pub async fn get_todo_by_reference(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(reference): Path<String>,
+) -> APIResponse<PublicTodo> {
+    let (shortcode, project_todo_number) = parse_reference(&reference)
+        .ok_or_else(|| ErrorResponse::from(StatusCode::BAD_REQUEST, "Invalid reference."))?;
+
+    let record = sqlx::query_as!(
+        TodoRecord,
+        "
+            SELECT todo.* FROM todo_reference
+            JOIN project ON project.id = todo_reference.project_id
+            JOIN todo ON todo.id = todo_reference.todo_id
+            WHERE project.account_id = $1
+                AND (project.shortcode = $2 OR project.id IN (
+                    SELECT project_id FROM project_shortcode_alias
+                    WHERE account_id = $1 AND shortcode = $2
+                ))
+                AND todo_reference.project_todo_number = $3
+        ",
+        account_id,
+        shortcode,
+        project_todo_number
+    )
+    .fetch_optional(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to resolve reference.",
+        )
+    })?
+    .ok_or_else(|| ErrorResponse::from(StatusCode::NOT_FOUND, "The todo does not exist."))?;
+    todo_response(&pg_pool, record).await
+}
+
+#[derive(Serialize)]
+pub struct PublicTodoReference {
+    reference: String,
+    current: bool,
+}
+
+pub async fn get_todo_references(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+) -> APIResponse<Vec<PublicTodoReference>> {
+    validate_account_has_todo(&pg_pool, account_id, todo_id).await?;
+
+    Ok(sqlx::query!(
+        "
+            SELECT
+                project.shortcode,
+                todo_reference.project_todo_number,
+                todo.project_id IS NOT DISTINCT FROM todo_reference.project_id
+                    AND todo.project_todo_number IS NOT DISTINCT FROM todo_reference.project_todo_number
+                    AS \"current!\"
+            FROM todo_reference
+            JOIN project ON project.id = todo_reference.project_id
+            JOIN todo ON todo.id = todo_reference.todo_id
+            WHERE todo_reference.todo_id = $1
+            ORDER BY todo_reference.created_at
+        ",
+        todo_id
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch references.",
+        )
+    })?
+    .into_iter()
+    .map(|record| PublicTodoReference {
+        reference: format_reference(&record.shortcode, record.project_todo_number),
+        current: record.current,
+    })
+    .collect::<Vec<_>>()
+    .into())
+}
+
+pub(super) async fn fetch_todo(
+    pg_pool: &PgPool,
+    account_id: i32,
+    todo_id: i64,
+) -> APIResult<TodoRecord> {
+    sqlx::query_as!(
+        TodoRecord,
+        "
+            SELECT * FROM todo
+            WHERE id = $1 AND account_id = $2
+        ",
+        todo_id,
+        account_id
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch todo.")
+    })?
+    .ok_or_else(|| ErrorResponse::from(StatusCode::NOT_FOUND, "The todo does not exist."))
 }
 
 #[derive(Deserialize)]
-pub struct CreateOrUpdateTodoRequest {
+pub struct CreateTodoRequest {
     title: String,
     memo: Option<String>,
     completed_at: Option<DateTime<Utc>>,
     deadline: Option<DateTime<Utc>>,
+    deadline_date: Option<NaiveDate>,
+    start_date: Option<NaiveDate>,
+    scheduled_date: Option<NaiveDate>,
     project_id: Option<i64>,
+    parent_todo_id: Option<i64>,
+    priority: Option<i16>,
+    recurrence: Option<String>,
+    #[serde(default)]
+    recurs_from_completion: bool,
+    /// A workflow state of the project of the todo. Defaults to the first
+    /// open state, or the first closed state for completed todos.
+    workflow_state_id: Option<i64>,
+    section_id: Option<i64>,
+    milestone_id: Option<i64>,
+    iteration_id: Option<i64>,
+}
+
+/// What to do with the open subtasks of a todo when it is completed.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenSubtasksPolicy {
+    /// Leave open subtasks as they are.
+    #[default]
+    Ignore,
+    /// Complete open subtasks together with the todo.
+    Complete,
+    /// Refuse to complete the todo while it has open subtasks.
+    Block,
+}
+
+/// Partial update of a todo. Missing fields are left unchanged, while fields
+/// explicitly set to `null` are cleared.
+#[derive(Deserialize)]
+pub struct UpdateTodoRequest {
+    title: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    memo: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    completed_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    deadline: Option<Option<DateTime<Utc>>>,
+    /// Setting a date-only deadline clears the timed deadline, and vice versa.
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    deadline_date: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    start_date: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    scheduled_date: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    project_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    parent_todo_id: Option<Option<i64>>,
+    priority: Option<i16>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    recurrence: Option<Option<String>>,
+    recurs_from_completion: Option<bool>,
+    /// Moving the todo to another workflow state completes or reopens it
+    /// according to the category of the state.
+    workflow_state_id: Option<i64>,
+    /// Moving the todo to another project removes it from its section,
+    /// milestone, and iteration.
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    section_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    milestone_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    iteration_id: Option<Option<i64>>,
+    #[serde(default)]
+    open_subtasks: OpenSubtasksPolicy,
+}
+
+pub(super) fn validate_single_deadline(
+    deadline: Option<DateTime<Utc>>,
+    deadline_date: Option<NaiveDate>,
+) -> APIResult<()> {
+    if deadline.is_some() && deadline_date.is_some() {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "A todo cannot have both a deadline and a date-only deadline.",
+        ));
+    }
+
+    Ok(())
 }
 
-async fn validate_account_has_project(
+pub(super) fn validate_priority(priority: i16) -> APIResult<()> {
+    if !(0..=4).contains(&priority) {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "Priority must be between 0 and 4.",
+        ));
+    }
+
+    Ok(())
+}
+
+pub(super) async fn validate_account_has_project(
     pg_pool: &PgPool,
     account_id: i32,
     project_id: i64,
@@ -92,7 +772,7 @@ async fn validate_account_has_project(
     Ok(())
 }
 
-async fn validate_account_has_todo(
+pub(super) async fn validate_account_has_todo(
     pg_pool: &PgPool,
     account_id: i32,
     todo_id: i64,
@@ -126,24 +806,268 @@ async fn validate_account_has_todo(
     }
 }
 
+fn project_archived_error() -> ErrorResponse {
+    ErrorResponse::from(StatusCode::CONFLICT, "The project is archived.")
+}
+
+/// Validates that a project belongs to the account and can be modified, which
+/// is not the case while it is archived.
+pub(super) async fn validate_account_can_edit_project(
+    pg_pool: &PgPool,
+    account_id: i32,
+    project_id: i64,
+) -> APIResult<()> {
+    validate_account_has_project(pg_pool, account_id, project_id).await?;
+
+    let archived = sqlx::query!(
+        "SELECT archived_at IS NOT NULL AS \"archived!\" FROM project WHERE id = $1",
+        project_id
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map(|record| record.archived)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate project.",
+        )
+    })?;
+
+    if archived {
+        return Err(project_archived_error());
+    }
+
+    Ok(())
+}
+
+/// Validates that a todo belongs to the account and can be modified, which is
+/// not the case while its project is archived.
+pub(super) async fn validate_account_can_edit_todo(
+    pg_pool: &PgPool,
+    account_id: i32,
+    todo_id: i64,
+) -> APIResult<()> {
+    validate_account_has_todo(pg_pool, account_id, todo_id).await?;
+
+    let archived = sqlx::query!(
+        "
+            SELECT EXISTS (
+                SELECT 1 FROM todo
+                JOIN project ON project.id = todo.project_id
+                WHERE todo.id = $1 AND project.archived_at IS NOT NULL
+            ) AS \"archived!\"
+        ",
+        todo_id
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map(|record| record.archived)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate todo belongs to user.",
+        )
+    })?;
+
+    if archived {
+        return Err(project_archived_error());
+    }
+
+    Ok(())
+}
+
+/// Validates that a todo can become a subtask of `parent_todo_id`, which must
+/// not be the todo itself or one of its subtasks. The check runs in the
+/// transaction updating the todo, after taking the lock of the account.
+async fn validate_parent_todo(
+    transaction: &mut Transaction<'_, Postgres>,
+    account_id: i32,
+    todo_id: i64,
+    parent_todo_id: i64,
+) -> APIResult<()> {
+    let map_err = |_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate parent todo.",
+        )
+    };
+
+    // Serialize changes to the subtasks of the account, so that two
+    // concurrent changes cannot create a cycle together.
+    sqlx::query!("SELECT pg_advisory_xact_lock($1)", i64::from(account_id))
+        .execute(&mut *transaction)
+        .await
+        .map_err(map_err)?;
+
+    let creates_cycle = sqlx::query!(
+        "SELECT $2 IN (SELECT todo_subtree($1)) AS \"creates_cycle!\"",
+        todo_id,
+        parent_todo_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map(|record| record.creates_cycle)
+    .map_err(map_err)?;
+
+    if creates_cycle {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "A todo cannot be a subtask of itself or of its subtasks.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Locks a todo for an update and returns whether it is completed, so that
+/// the caller can tell whether the update completed it.
+pub(super) async fn lock_todo_for_completion(
+    transaction: &mut Transaction<'_, Postgres>,
+    todo_id: i64,
+) -> APIResult<bool> {
+    sqlx::query!(
+        "SELECT completed_at IS NOT NULL AS \"completed!\" FROM todo WHERE id = $1 FOR UPDATE",
+        todo_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map(|record| record.completed)
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update todo.")
+    })
+}
+
+/// Applies the consequences of a todo becoming completed, whether directly or
+/// by moving it to a closed workflow state: the policy for its open subtasks,
+/// and the next occurrence of a recurring todo.
+pub(super) async fn handle_todo_completed(
+    transaction: &mut Transaction<'_, Postgres>,
+    todo_id: i64,
+    open_subtasks: OpenSubtasksPolicy,
+) -> APIResult<()> {
+    match open_subtasks {
+        OpenSubtasksPolicy::Ignore => {}
+        OpenSubtasksPolicy::Block => {
+            let open_subtask_count = sqlx::query!(
+                "
+                    SELECT COUNT(*) AS \"count!\" FROM todo
+                    WHERE id IN (SELECT todo_subtree($1)) AND id <> $1 AND completed_at IS NULL
+                ",
+                todo_id
+            )
+            .fetch_one(&mut *transaction)
+            .await
+            .map(|record| record.count)
+            .map_err(|_err| {
+                ErrorResponse::from(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to fetch subtasks.",
+                )
+            })?;
+
+            if open_subtask_count > 0 {
+                return Err(ErrorResponse::from(
+                    StatusCode::CONFLICT,
+                    "The todo has open subtasks.",
+                ));
+            }
+        }
+        OpenSubtasksPolicy::Complete => {
+            sqlx::query!(
+                "
+                    UPDATE todo
+                    SET completed_at = (SELECT completed_at FROM todo WHERE id = $1)
+                    WHERE id IN (SELECT todo_subtree($1)) AND id <> $1 AND completed_at IS NULL
+                ",
+                todo_id
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_err| {
+                ErrorResponse::from(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to complete subtasks.",
+                )
+            })?;
+        }
+    }
+
+    create_next_occurrence(transaction, todo_id).await
+}
+
+/// Marks a todo as modified when data shown on it, but stored outside of the
+/// `todo` row, changes.
+pub(super) async fn touch_todo(pg_pool: &PgPool, todo_id: i64) -> APIResult<()> {
+    sqlx::query!(
+        "
+            UPDATE todo
+            SET updated_at = CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+            WHERE id = $1
+        ",
+        todo_id
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update todo.")
+    })?;
+
+    Ok(())
+}
+
 pub async fn post_todos(
     AccountId(account_id): AccountId,
     Extension(pg_pool): Extension<PgPool>,
-    Json(req): Json<CreateOrUpdateTodoRequest>,
+    Json(req): Json<CreateTodoRequest>,
 ) -> APIResponse<PublicTodo> {
     if let Some(project_id) = req.project_id {
-        validate_account_has_project(&pg_pool, account_id, project_id).await?;
+        validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+    }
+    if let Some(parent_todo_id) = req.parent_todo_id {
+        validate_account_has_todo(&pg_pool, account_id, parent_todo_id).await?;
+    }
+    if let Some(priority) = req.priority {
+        validate_priority(priority)?;
+    }
+    validate_single_deadline(req.deadline, req.deadline_date)?;
+    if let Some(recurrence) = &req.recurrence {
+        Recurrence::parse(recurrence)?;
+    }
+    if let Some(workflow_state_id) = req.workflow_state_id {
+        validate_workflow_state(&pg_pool, workflow_state_id, req.project_id).await?;
+    }
+    if let Some(section_id) = req.section_id {
+        validate_section(&pg_pool, section_id, req.project_id).await?;
+    }
+    if let Some(milestone_id) = req.milestone_id {
+        validate_milestone(&pg_pool, milestone_id, req.project_id).await?;
+    }
+    if let Some(iteration_id) = req.iteration_id {
+        validate_iteration(&pg_pool, iteration_id, req.project_id).await?;
     }
 
-    let record = sqlx::query!(
+    let record = sqlx::query_as!(
+        TodoRecord,
         "
-            INSERT INTO todo (account_id, title, memo, completed_at, deadline, project_id, project_todo_number)
+            INSERT INTO todo (account_id, title, memo, completed_at, deadline, project_id, project_todo_number, parent_todo_id, priority, sort_rank, deadline_date, start_date, scheduled_date, recurrence, recurs_from_completion, workflow_state_id, section_id, milestone_id, iteration_id)
             VALUES (
                 $1, $2, $3, $4, $5, CAST($6 AS BIGINT),
-                CASE WHEN $6 IS NULL THEN NULL ELSE (
-                    SELECT COALESCE(MAX(project_todo_number), 0) + 1 FROM todo
-                    WHERE project_id = $6
-                ) END
+                CASE WHEN $6 IS NULL THEN NULL ELSE allocate_project_todo_number($6) END,
+                $7,
+                COALESCE($8::SMALLINT, 0),
+                (
+                    SELECT COALESCE(MAX(sort_rank), 0) + $9 FROM todo
+                    WHERE account_id = $1 AND project_id IS NOT DISTINCT FROM $6
+                ),
+                $10,
+                $11,
+                $12,
+                $13,
+                $14,
+                $15,
+                $16,
+                $17,
+                $18
             )
             RETURNING *
         ",
@@ -152,83 +1076,310 @@ pub async fn post_todos(
         &req.memo.unwrap_or_else(|| "".to_string()),
         req.completed_at.map(|datetime| datetime.naive_utc()),
         req.deadline.map(|datetime| datetime.naive_utc()),
-        req.project_id
+        req.project_id,
+        req.parent_todo_id,
+        req.priority,
+        RANK_GAP,
+        req.deadline_date,
+        req.start_date,
+        req.scheduled_date,
+        req.recurrence,
+        req.recurs_from_completion,
+        req.workflow_state_id,
+        req.section_id,
+        req.milestone_id,
+        req.iteration_id
     )
     .fetch_one(&pg_pool)
     .await
     .map_err(|_| ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create todo."))?;
 
-    Ok(PublicTodo {
-        id: record.id,
-        title: record.title,
-        memo: record.memo,
-        project_id: record.project_id,
-        project_todo_number: record.project_todo_number,
-        completed_at: record
-            .completed_at
-            .map(|datetime| DateTime::<Utc>::from_utc(datetime, Utc)),
-        deadline: record
-            .deadline
-            .map(|datetime| DateTime::<Utc>::from_utc(datetime, Utc)),
+    todo_response(&pg_pool, record).await
+}
+
+/// Duplicates a todo. Its checklist, unchecked, and its files are copied
+/// when requested.
+#[derive(Deserialize)]
+pub struct DuplicateTodoRequest {
+    #[serde(default)]
+    include_checklist: bool,
+    #[serde(default)]
+    include_files: bool,
+}
+
+fn map_duplicate_todo_error(_err: sqlx::Error) -> ErrorResponse {
+    ErrorResponse::from(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to duplicate todo.",
+    )
+}
+
+/// Creates an open copy of a todo at the end of its list, with its tags
+/// attached but without its subtasks. A copy in a project gets the next
+/// number of the project.
+pub async fn post_todo_duplicate(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+    Json(req): Json<DuplicateTodoRequest>,
+) -> APIResponse<PublicTodo> {
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    let mut transaction = pg_pool.begin().await.map_err(map_duplicate_todo_error)?;
+
+    let copy_id = sqlx::query!(
+        "
+            INSERT INTO todo (
+                account_id, title, memo, deadline, deadline_date, start_date, scheduled_date,
+                project_id, project_todo_number, parent_todo_id, priority, sort_rank,
+                recurrence, recurs_from_completion, workflow_state_id, section_id, milestone_id,
+                iteration_id
+            )
+            SELECT
+                todo.account_id,
+                todo.title,
+                todo.memo,
+                todo.deadline,
+                todo.deadline_date,
+                todo.start_date,
+                todo.scheduled_date,
+                todo.project_id,
+                CASE WHEN todo.project_id IS NULL THEN NULL ELSE allocate_project_todo_number(todo.project_id) END,
+                todo.parent_todo_id,
+                todo.priority,
+                (
+                    SELECT COALESCE(MAX(list.sort_rank), 0) + $2 FROM todo list
+                    WHERE list.account_id = todo.account_id
+                        AND list.project_id IS NOT DISTINCT FROM todo.project_id
+                ),
+                todo.recurrence,
+                todo.recurs_from_completion,
+                CASE WHEN workflow_state.category = 'closed' THEN NULL ELSE todo.workflow_state_id END,
+                todo.section_id,
+                todo.milestone_id,
+                todo.iteration_id
+            FROM todo
+            LEFT JOIN workflow_state ON workflow_state.id = todo.workflow_state_id
+            WHERE todo.id = $1
+            RETURNING id
+        ",
+        todo_id,
+        RANK_GAP
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .map_err(map_duplicate_todo_error)?
+    .id;
+
+    sqlx::query!(
+        "
+            INSERT INTO todo_tag (todo_id, tag_id)
+            SELECT $2, tag_id FROM todo_tag WHERE todo_id = $1
+        ",
+        todo_id,
+        copy_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(map_duplicate_todo_error)?;
+
+    if req.include_checklist {
+        sqlx::query!(
+            "
+                INSERT INTO checklist_item (todo_id, position, text)
+                SELECT $2, position, text FROM checklist_item WHERE todo_id = $1
+            ",
+            todo_id,
+            copy_id
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(map_duplicate_todo_error)?;
     }
-    .into())
+
+    if req.include_files {
+        // The copies refer to the same stored files.
+        sqlx::query!(
+            "
+                INSERT INTO todo_file (todo_id, original_filename, memo, file_accessor)
+                SELECT $2, original_filename, memo, file_accessor FROM todo_file
+                WHERE todo_id = $1
+            ",
+            todo_id,
+            copy_id
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(map_duplicate_todo_error)?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .map_err(map_duplicate_todo_error)?;
+
+    let record = fetch_todo(&pg_pool, account_id, copy_id).await?;
+
+    todo_response(&pg_pool, record).await
 }
 
 pub async fn post_todo(
     AccountId(account_id): AccountId,
     Extension(pg_pool): Extension<PgPool>,
     Path(todo_id): Path<i64>,
-    Json(req): Json<CreateOrUpdateTodoRequest>,
+    IfMatch(expected_version): IfMatch,
+    Json(req): Json<UpdateTodoRequest>,
 ) -> APIResponse<PublicTodo> {
-    if let Some(project_id) = req.project_id {
-        validate_account_has_project(&pg_pool, account_id, project_id).await?;
+    if let Some(Some(project_id)) = req.project_id {
+        validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
     }
 
-    validate_account_has_todo(&pg_pool, account_id, todo_id).await?;
+    validate_account_can_edit_todo(&pg_pool, account_id, todo_id).await?;
+
+    if let Some(Some(parent_todo_id)) = req.parent_todo_id {
+        validate_account_has_todo(&pg_pool, account_id, parent_todo_id).await?;
+    }
+    if let Some(priority) = req.priority {
+        validate_priority(priority)?;
+    }
+    validate_single_deadline(req.deadline.flatten(), req.deadline_date.flatten())?;
+    if let Some(Some(recurrence)) = &req.recurrence {
+        Recurrence::parse(recurrence)?;
+    }
+    if req.workflow_state_id.is_some()
+        || matches!(req.section_id, Some(Some(_)))
+        || matches!(req.milestone_id, Some(Some(_)))
+        || matches!(req.iteration_id, Some(Some(_)))
+    {
+        let project_id = match req.project_id {
+            Some(project_id) => project_id,
+            None => fetch_todo(&pg_pool, account_id, todo_id).await?.project_id,
+        };
+        if let Some(workflow_state_id) = req.workflow_state_id {
+            validate_workflow_state(&pg_pool, workflow_state_id, project_id).await?;
+        }
+        if let Some(Some(section_id)) = req.section_id {
+            validate_section(&pg_pool, section_id, project_id).await?;
+        }
+        if let Some(Some(milestone_id)) = req.milestone_id {
+            validate_milestone(&pg_pool, milestone_id, project_id).await?;
+        }
+        if let Some(Some(iteration_id)) = req.iteration_id {
+            validate_iteration(&pg_pool, iteration_id, project_id).await?;
+        }
+    }
+
+    let completed_at = req
+        .completed_at
+        .flatten()
+        .map(|datetime| datetime.naive_utc());
+
+    let mut transaction = pg_pool.begin().await.map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update todo.")
+    })?;
 
-    let record = sqlx::query!(
+    if let Some(Some(parent_todo_id)) = req.parent_todo_id {
+        validate_parent_todo(&mut transaction, account_id, todo_id, parent_todo_id).await?;
+    }
+
+    let was_completed = lock_todo_for_completion(&mut transaction, todo_id).await?;
+
+    let record = sqlx::query_as!(
+        TodoRecord,
         "
             UPDATE todo
-            SET title = $2,
-                memo = $3,
-                completed_at = $4,
-                deadline = $5,
-                project_id = CAST($6 AS BIGINT),
+            SET title = COALESCE($2, title),
+                memo = CASE WHEN $3 THEN COALESCE($4, '') ELSE memo END,
+                completed_at = CASE WHEN $5 THEN $6 ELSE completed_at END,
+                deadline = CASE WHEN $7 THEN $8 WHEN $17::DATE IS NOT NULL THEN NULL ELSE deadline END,
+                deadline_date =
+                    CASE WHEN $16 THEN $17 WHEN $8::TIMESTAMP IS NOT NULL THEN NULL ELSE deadline_date END,
+                start_date = CASE WHEN $18 THEN $19 ELSE start_date END,
+                scheduled_date = CASE WHEN $20 THEN $21 ELSE scheduled_date END,
+                recurrence = CASE WHEN $22 THEN $23 ELSE recurrence END,
+                recurs_from_completion = COALESCE($24, recurs_from_completion),
+                workflow_state_id = COALESCE($25, workflow_state_id),
+                section_id = CASE WHEN $26 THEN $27 ELSE section_id END,
+                milestone_id = CASE WHEN $28 THEN $29 ELSE milestone_id END,
+                iteration_id = CASE WHEN $30 THEN $31 ELSE iteration_id END,
+                project_id = CASE WHEN $9 THEN CAST($10 AS BIGINT) ELSE project_id END,
                 project_todo_number =
-                    CASE WHEN project_id = $6 THEN project_todo_number ELSE
-                    CASE WHEN $6 IS NULL THEN NULL ELSE
-                    (
-                        SELECT COALESCE(MAX(project_todo_number), 0) + 1 FROM todo
-                        WHERE project_id = $6
-                    ) END END
-            WHERE id = $1
+                    CASE WHEN NOT $9 OR project_id IS NOT DISTINCT FROM $10 THEN project_todo_number ELSE
+                    CASE WHEN $10 IS NULL THEN NULL ELSE allocate_project_todo_number($10) END END,
+                parent_todo_id = CASE WHEN $12 THEN $13 ELSE parent_todo_id END,
+                priority = COALESCE($14::SMALLINT, priority),
+                sort_rank =
+                    CASE WHEN NOT $9 OR project_id IS NOT DISTINCT FROM $10 THEN sort_rank ELSE (
+                        SELECT COALESCE(MAX(list.sort_rank), 0) + $15 FROM todo list
+                        WHERE list.account_id = todo.account_id
+                            AND list.project_id IS NOT DISTINCT FROM $10
+                    ) END
+            WHERE id = $1 AND ($11::INT IS NULL OR version = $11)
             RETURNING *
         ",
         todo_id,
-        &req.title,
-        &req.memo.unwrap_or_else(|| "".to_string()),
-        req.completed_at.map(|datetime| datetime.naive_utc()),
-        req.deadline.map(|datetime| datetime.naive_utc()),
-        req.project_id
+        req.title,
+        req.memo.is_some(),
+        req.memo.flatten(),
+        req.completed_at.is_some(),
+        completed_at,
+        req.deadline.is_some(),
+        req.deadline.flatten().map(|datetime| datetime.naive_utc()),
+        req.project_id.is_some(),
+        req.project_id.flatten(),
+        expected_version,
+        req.parent_todo_id.is_some(),
+        req.parent_todo_id.flatten(),
+        req.priority,
+        RANK_GAP,
+        req.deadline_date.is_some(),
+        req.deadline_date.flatten(),
+        req.start_date.is_some(),
+        req.start_date.flatten(),
+        req.scheduled_date.is_some(),
+        req.scheduled_date.flatten(),
+        req.recurrence.is_some(),
+        req.recurrence.flatten(),
+        req.recurs_from_completion,
+        req.workflow_state_id,
+        req.section_id.is_some(),
+        req.section_id.flatten(),
+        req.milestone_id.is_some(),
+        req.milestone_id.flatten(),
+        req.iteration_id.is_some(),
+        req.iteration_id.flatten()
     )
-    .fetch_one(&pg_pool)
+    .fetch_optional(&mut transaction)
     .await
     .map_err(|_| {
-        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create todo.")
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update todo.")
     })?;
 
-    Ok(PublicTodo {
-        id: record.id,
-        title: record.title,
-        memo: record.memo,
-        project_id: record.project_id,
-        project_todo_number: record.project_todo_number,
-        completed_at: record
-            .completed_at
-            .map(|datetime| DateTime::<Utc>::from_utc(datetime, Utc)),
-        deadline: record
-            .deadline
-            .map(|datetime| DateTime::<Utc>::from_utc(datetime, Utc)),
+    let record = match record {
+        Some(record) => record,
+        None => {
+            drop(transaction);
+            let current = fetch_todo(&pg_pool, account_id, todo_id).await?;
+            let version = current.version;
+            return Err(ErrorResponse::precondition_failed(
+                to_public_todo(&pg_pool, current).await?,
+                version,
+            ));
+        }
+    };
+    if req.deadline.is_some() || req.deadline_date.is_some() {
+        reschedule_deadline_reminders(&mut transaction, todo_id).await?;
     }
-    .into())
+
+    // The todo may also be completed by moving it to a closed workflow state,
+    // in which case `completed_at` is set by the database.
+    if !was_completed && record.completed_at.is_some() {
+        handle_todo_completed(&mut transaction, todo_id, req.open_subtasks).await?;
+    }
+
+    transaction.commit().await.map_err(|_err| {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update todo.")
+    })?;
+
+    todo_response(&pg_pool, record).await
 }
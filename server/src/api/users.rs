@@ -1,10 +1,11 @@
-use super::{APIResponse, ErrorResponse};
+use super::{deserialize_optional_field, APIResponse, APIResult, ErrorResponse};
 use axum::{extract::Extension, http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
 
 use crate::{
     auth::AccountId,
+    channels::resolve_webhook_url,
     session::{Session, SessionData},
 };
 
@@ -56,11 +57,12 @@ pub async fn post_login(
 pub struct PublicUser {
     username: String,
     display_name: String,
-}
-
-#[derive(Serialize)]
-struct GetUserResponse {
-    user: Option<PublicUser>,
+    /// IANA name of the time zone of the user, such as `Europe/Berlin`.
+    time_zone: String,
+    /// Where reminders sent by email are delivered.
+    email: Option<String>,
+    /// Where reminders sent by webhook are posted.
+    webhook_url: Option<String>,
 }
 
 pub async fn get_user(
@@ -74,7 +76,7 @@ pub async fn get_user(
 
     sqlx::query!(
         "
-            SELECT username, display_name FROM account
+            SELECT username, display_name, time_zone, email, webhook_url FROM account
             WHERE id = $1
         ",
         &account_id
@@ -98,6 +100,9 @@ pub async fn get_user(
         Some(PublicUser {
             username: user.username,
             display_name: user.display_name,
+            time_zone: user.time_zone,
+            email: user.email,
+            webhook_url: user.webhook_url,
         })
         .into()
     })
@@ -108,21 +113,73 @@ pub struct CreateUserRequest {
     username: String,
     display_name: Option<String>,
     password: String,
+    time_zone: Option<String>,
 }
 
+/// Partial update of the current user. Missing fields are left unchanged.
+/// Setting `display_name` to `null` resets it to the username.
 #[derive(Deserialize)]
 pub struct UpdateUserRequest {
     username: Option<String>,
-    display_name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    display_name: Option<Option<String>>,
     password: Option<String>,
+    time_zone: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    email: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    webhook_url: Option<Option<String>>,
 }
 
 const BCRYPT_COST: u32 = 10;
 
+fn validate_email(email: &str) -> APIResult<()> {
+    email
+        .parse::<lettre::Address>()
+        .map(|_address| ())
+        .map_err(|_err| ErrorResponse::from(StatusCode::BAD_REQUEST, "Invalid email address."))
+}
+
+async fn validate_webhook_url(webhook_url: &str) -> APIResult<()> {
+    resolve_webhook_url(webhook_url)
+        .await
+        .map(|_resolved| ())
+        .map_err(|err| ErrorResponse::from(StatusCode::BAD_REQUEST, &err.to_string()))
+}
+
+async fn validate_time_zone(pg_pool: &PgPool, time_zone: &str) -> APIResult<()> {
+    let exists = sqlx::query!(
+        "SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1) AS \"exists!\"",
+        time_zone
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map(|record| record.exists)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate time zone.",
+        )
+    })?;
+
+    if !exists {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "Unknown time zone.",
+        ));
+    }
+
+    Ok(())
+}
+
 pub async fn post_users(
     Extension(pg_pool): Extension<PgPool>,
     Json(req): Json<CreateUserRequest>,
 ) -> APIResponse<PublicUser> {
+    if let Some(time_zone) = &req.time_zone {
+        validate_time_zone(&pg_pool, time_zone).await?;
+    }
+
     let hash_result = bcrypt::hash(req.password, BCRYPT_COST).unwrap();
     let same_username_result = sqlx::query!(
         "SELECT * FROM account WHERE username = $1 LIMIT 1",
@@ -144,13 +201,14 @@ pub async fn post_users(
     }
     let created_user = sqlx::query!(
         "
-            INSERT INTO account (username, display_name, password_hash_and_salt)
-            VALUES ($1, $2, $3)
+            INSERT INTO account (username, display_name, password_hash_and_salt, time_zone)
+            VALUES ($1, $2, $3, COALESCE($4, 'UTC'))
             RETURNING *
         ",
         &req.username,
         req.display_name.as_ref().unwrap_or(&req.username),
-        &hash_result
+        &hash_result,
+        req.time_zone
     )
     .fetch_one(&pg_pool)
     .await
@@ -158,6 +216,9 @@ pub async fn post_users(
     Ok(PublicUser {
         username: created_user.username,
         display_name: created_user.display_name,
+        time_zone: created_user.time_zone,
+        email: created_user.email,
+        webhook_url: created_user.webhook_url,
     }
     .into())
 }
@@ -167,6 +228,16 @@ pub async fn post_user(
     Extension(pg_pool): Extension<PgPool>,
     Json(req): Json<UpdateUserRequest>,
 ) -> APIResponse<PublicUser> {
+    if let Some(time_zone) = &req.time_zone {
+        validate_time_zone(&pg_pool, time_zone).await?;
+    }
+    if let Some(Some(email)) = &req.email {
+        validate_email(email)?;
+    }
+    if let Some(Some(webhook_url)) = &req.webhook_url {
+        validate_webhook_url(webhook_url).await?;
+    }
+
     let password_hash_and_salt = req
         .password
         .map(|password| bcrypt::hash(password, BCRYPT_COST).unwrap());
@@ -175,15 +246,24 @@ pub async fn post_user(
         "
             UPDATE account
             SET username = COALESCE($2, username),
-                display_name = COALESCE($3, display_name),
-                password_hash_and_salt = COALESCE($4, password_hash_and_salt)
+                display_name = CASE WHEN $3 THEN COALESCE($4, $2, username) ELSE display_name END,
+                password_hash_and_salt = COALESCE($5, password_hash_and_salt),
+                time_zone = COALESCE($6, time_zone),
+                email = CASE WHEN $7 THEN $8 ELSE email END,
+                webhook_url = CASE WHEN $9 THEN $10 ELSE webhook_url END
             WHERE id = $1
             RETURNING *
         ",
         account_id,
         req.username,
-        req.display_name,
-        password_hash_and_salt
+        req.display_name.is_some(),
+        req.display_name.flatten(),
+        password_hash_and_salt,
+        req.time_zone,
+        req.email.is_some(),
+        req.email.flatten(),
+        req.webhook_url.is_some(),
+        req.webhook_url.flatten()
     )
     .fetch_one(&pg_pool)
     .await
@@ -192,6 +272,9 @@ pub async fn post_user(
     Ok(PublicUser {
         username: updated_user.username,
         display_name: updated_user.display_name,
+        time_zone: updated_user.time_zone,
+        email: updated_user.email,
+        webhook_url: updated_user.webhook_url,
     }
     .into())
 }
@@ -0,0 +1,555 @@
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+
+use crate::auth::AccountId;
+
+use super::{
+    is_unique_violation,
+    todos::{
+        handle_todo_completed, validate_account_can_edit_project, validate_account_has_project,
+        validate_account_has_todo, OpenSubtasksPolicy,
+    },
+    APIResponse, APIResult, ErrorResponse,
+};
+
+/// Whether the todos in a workflow state are waiting, being worked on, or
+/// completed. Todos are completed exactly when their state is closed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StateCategory {
+    Open,
+    Active,
+    Closed,
+}
+
+impl StateCategory {
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            StateCategory::Open => "open",
+            StateCategory::Active => "active",
+            StateCategory::Closed => "closed",
+        }
+    }
+
+    fn parse(category: &str) -> Option<StateCategory> {
+        match category {
+            "open" => Some(StateCategory::Open),
+            "active" => Some(StateCategory::Active),
+            "closed" => Some(StateCategory::Closed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PublicWorkflowState {
+    id: i64,
+    project_id: i64,
+    state_name: String,
+    category: StateCategory,
+    position: i32,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct WorkflowStateRecord {
+    id: i64,
+    project_id: i64,
+    state_name: String,
+    category: String,
+    position: i32,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl From<WorkflowStateRecord> for PublicWorkflowState {
+    fn from(record: WorkflowStateRecord) -> Self {
+        PublicWorkflowState {
+            id: record.id,
+            project_id: record.project_id,
+            state_name: record.state_name,
+            category: StateCategory::parse(&record.category).unwrap_or(StateCategory::Open),
+            position: record.position,
+            created_at: DateTime::<Utc>::from_naive_utc_and_offset(record.created_at, Utc),
+            updated_at: DateTime::<Utc>::from_naive_utc_and_offset(record.updated_at, Utc),
+        }
+    }
+}
+
+fn map_workflow_state_write_error(err: sqlx::Error, message: &'static str) -> ErrorResponse {
+    if is_unique_violation(&err) {
+        ErrorResponse::from(
+            StatusCode::CONFLICT,
+            "A workflow state with the same name already exists in the project.",
+        )
+    } else {
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+/// Checks that a workflow state belongs to the given project, which is the
+/// project a todo is in or is moved to.
+pub(super) async fn validate_workflow_state(
+    pg_pool: &PgPool,
+    workflow_state_id: i64,
+    project_id: Option<i64>,
+) -> APIResult<()> {
+    let exists = sqlx::query!(
+        "
+            SELECT EXISTS (
+                SELECT 1 FROM workflow_state WHERE id = $1 AND project_id = $2
+            ) AS \"exists!\"
+        ",
+        workflow_state_id,
+        project_id
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map(|record| record.exists)
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate workflow state.",
+        )
+    })?;
+
+    if !exists {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The workflow state does not belong to the project of the todo.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Locks the project of the workflow states being changed, so that concurrent
+/// changes cannot together remove the last state of a category.
+async fn lock_project_workflow_states(
+    transaction: &mut Transaction<'_, Postgres>,
+    project_id: i64,
+) -> APIResult<()> {
+    sqlx::query!(
+        "SELECT id FROM project WHERE id = $1 FOR NO KEY UPDATE",
+        project_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to lock workflow states.",
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Validates that a project still has a closed workflow state and one that is
+/// not closed after a change of its states, since completing and reopening
+/// its todos moves them to such states.
+async fn validate_project_keeps_state_categories(
+    transaction: &mut Transaction<'_, Postgres>,
+    project_id: i64,
+) -> APIResult<()> {
+    let categories = sqlx::query!(
+        "
+            SELECT
+                COALESCE(bool_or(category = 'closed'), FALSE) AS \"has_closed!\",
+                COALESCE(bool_or(category <> 'closed'), FALSE) AS \"has_open!\"
+            FROM workflow_state
+            WHERE project_id = $1
+        ",
+        project_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to validate workflow states.",
+        )
+    })?;
+
+    if !categories.has_closed || !categories.has_open {
+        return Err(ErrorResponse::from(
+            StatusCode::CONFLICT,
+            "A project needs at least one closed workflow state and one that is not closed.",
+        ));
+    }
+
+    Ok(())
+}
+
+async fn fetch_workflow_states(
+    pg_pool: &PgPool,
+    project_id: i64,
+) -> APIResult<Vec<PublicWorkflowState>> {
+    Ok(sqlx::query_as!(
+        WorkflowStateRecord,
+        "
+            SELECT * FROM workflow_state
+            WHERE project_id = $1
+            ORDER BY position, id
+        ",
+        project_id
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch workflow states.",
+        )
+    })?
+    .into_iter()
+    .map(PublicWorkflowState::from)
+    .collect())
+}
+
+pub async fn get_workflow_states(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+) -> APIResponse<Vec<PublicWorkflowState>> {
+    validate_account_has_project(&pg_pool, account_id, project_id).await?;
+
+    Ok(fetch_workflow_states(&pg_pool, project_id).await?.into())
+}
+
+#[derive(Deserialize)]
+pub struct CreateWorkflowStateRequest {
+    state_name: String,
+    category: StateCategory,
+}
+
+/// Partial update of a workflow state. Changing the category completes or
+/// reopens the todos in the state.
+#[derive(Deserialize)]
+pub struct UpdateWorkflowStateRequest {
+    state_name: Option<String>,
+    category: Option<StateCategory>,
+}
+
+/// The new order of the workflow states of a project, containing every state
+/// exactly once.
+#[derive(Deserialize)]
+pub struct ReorderWorkflowStatesRequest {
+    state_ids: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteWorkflowStateQuery {
+    /// The state the todos in the deleted state are moved to. Required if
+    /// the state has todos.
+    replacement_state_id: Option<i64>,
+}
+
+pub async fn post_workflow_states(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Json(req): Json<CreateWorkflowStateRequest>,
+) -> APIResponse<PublicWorkflowState> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let state = sqlx::query_as!(
+        WorkflowStateRecord,
+        "
+            INSERT INTO workflow_state (project_id, state_name, category, position)
+            VALUES (
+                $1,
+                $2,
+                $3,
+                (SELECT COALESCE(MAX(position), -1) + 1 FROM workflow_state WHERE project_id = $1)
+            )
+            RETURNING *
+        ",
+        project_id,
+        req.state_name.trim(),
+        req.category.as_str()
+    )
+    .fetch_one(&pg_pool)
+    .await
+    .map_err(|err| map_workflow_state_write_error(err, "Failed to create workflow state."))?;
+
+    Ok(PublicWorkflowState::from(state).into())
+}
+
+pub async fn put_workflow_states(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(project_id): Path<i64>,
+    Json(req): Json<ReorderWorkflowStatesRequest>,
+) -> APIResponse<Vec<PublicWorkflowState>> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let current_state_ids = fetch_workflow_states(&pg_pool, project_id)
+        .await?
+        .into_iter()
+        .map(|state| state.id)
+        .collect::<HashSet<_>>();
+    let requested_state_ids = req.state_ids.iter().copied().collect::<HashSet<_>>();
+
+    if requested_state_ids.len() != req.state_ids.len() || requested_state_ids != current_state_ids
+    {
+        return Err(ErrorResponse::from(
+            StatusCode::BAD_REQUEST,
+            "The order must contain every workflow state of the project exactly once.",
+        ));
+    }
+
+    sqlx::query!(
+        "
+            UPDATE workflow_state
+            SET position = ordering.position - 1
+            FROM UNNEST($2::BIGINT[]) WITH ORDINALITY AS ordering(id, position)
+            WHERE workflow_state.id = ordering.id AND workflow_state.project_id = $1
+        ",
+        project_id,
+        &req.state_ids
+    )
+    .execute(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to reorder workflow states.",
+        )
+    })?;
+
+    Ok(fetch_workflow_states(&pg_pool, project_id).await?.into())
+}
+
+pub async fn post_workflow_state(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, state_id)): Path<(i64, i64)>,
+    Json(req): Json<UpdateWorkflowStateRequest>,
+) -> APIResponse<PublicWorkflowState> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    let mut transaction = pg_pool.begin().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update workflow state.",
+        )
+    })?;
+
+    lock_project_workflow_states(&mut transaction, project_id).await?;
+
+    let state = sqlx::query_as!(
+        WorkflowStateRecord,
+        "
+            UPDATE workflow_state
+            SET state_name = COALESCE($3, state_name),
+                category = COALESCE($4, category)
+            WHERE id = $1 AND project_id = $2
+            RETURNING *
+        ",
+        state_id,
+        project_id,
+        req.state_name.as_deref().map(str::trim),
+        req.category.map(|category| category.as_str())
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .map_err(|err| map_workflow_state_write_error(err, "Failed to update workflow state."))?
+    .ok_or_else(|| {
+        ErrorResponse::from(StatusCode::NOT_FOUND, "The workflow state does not exist.")
+    })?;
+
+    if req.category.is_some() {
+        validate_project_keeps_state_categories(&mut transaction, project_id).await?;
+    }
+
+    // Complete or reopen the todos in the state to match its category.
+    let changed_todo_ids = sqlx::query!(
+        "
+            UPDATE todo
+            SET completed_at = CASE
+                WHEN $2 = 'closed' THEN COALESCE(completed_at, CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+                ELSE NULL
+            END
+            WHERE workflow_state_id = $1 AND (completed_at IS NOT NULL) <> ($2 = 'closed')
+            RETURNING id
+        ",
+        state_id,
+        &state.category
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update todos of workflow state.",
+        )
+    })?;
+
+    if state.category == StateCategory::Closed.as_str() {
+        for todo in changed_todo_ids {
+            handle_todo_completed(&mut transaction, todo.id, OpenSubtasksPolicy::Ignore).await?;
+        }
+    }
+
+    transaction.commit().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update workflow state.",
+        )
+    })?;
+
+    Ok(PublicWorkflowState::from(state).into())
+}
+
+pub async fn delete_workflow_state(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path((project_id, state_id)): Path<(i64, i64)>,
+    Query(query): Query<DeleteWorkflowStateQuery>,
+) -> APIResponse<()> {
+    validate_account_can_edit_project(&pg_pool, account_id, project_id).await?;
+
+    if let Some(replacement_state_id) = query.replacement_state_id {
+        if replacement_state_id == state_id {
+            return Err(ErrorResponse::from(
+                StatusCode::BAD_REQUEST,
+                "A workflow state cannot replace itself.",
+            ));
+        }
+        validate_workflow_state(&pg_pool, replacement_state_id, Some(project_id)).await?;
+    }
+
+    let mut transaction = pg_pool.begin().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete workflow state.",
+        )
+    })?;
+
+    lock_project_workflow_states(&mut transaction, project_id).await?;
+
+    // Moving the todos to a closed state completes them, which the database
+    // does when syncing `completed_at` with the state.
+    let moved = sqlx::query!(
+        "
+            UPDATE todo
+            SET workflow_state_id = $2
+            FROM todo previous
+            WHERE todo.workflow_state_id = $1 AND previous.id = todo.id
+            RETURNING
+                todo.id,
+                previous.completed_at IS NULL AND todo.completed_at IS NOT NULL AS \"completed!\"
+        ",
+        state_id,
+        query.replacement_state_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to move todos of workflow state.",
+        )
+    })?;
+
+    if !moved.is_empty() && query.replacement_state_id.is_none() {
+        return Err(ErrorResponse::from(
+            StatusCode::CONFLICT,
+            "The workflow state has todos. Choose a state to move them to.",
+        ));
+    }
+
+    let deleted = sqlx::query!(
+        "
+            DELETE FROM workflow_state
+            WHERE id = $1 AND project_id = $2
+        ",
+        state_id,
+        project_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete workflow state.",
+        )
+    })?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(ErrorResponse::from(
+            StatusCode::NOT_FOUND,
+            "The workflow state does not exist.",
+        ));
+    }
+
+    validate_project_keeps_state_categories(&mut transaction, project_id).await?;
+
+    // Only after the state is gone, so that next occurrences of completed
+    // recurring todos are not created in it.
+    for todo in moved.into_iter().filter(|todo| todo.completed) {
+        handle_todo_completed(&mut transaction, todo.id, OpenSubtasksPolicy::Ignore).await?;
+    }
+
+    transaction.commit().await.map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete workflow state.",
+        )
+    })?;
+
+    Ok(().into())
+}
+
+#[derive(Serialize)]
+pub struct PublicStateTransition {
+    from_workflow_state_id: Option<i64>,
+    to_workflow_state_id: Option<i64>,
+    transitioned_at: DateTime<Utc>,
+}
+
+/// The workflow state changes of a todo, oldest first.
+pub async fn get_todo_transitions(
+    AccountId(account_id): AccountId,
+    Extension(pg_pool): Extension<PgPool>,
+    Path(todo_id): Path<i64>,
+) -> APIResponse<Vec<PublicStateTransition>> {
+    validate_account_has_todo(&pg_pool, account_id, todo_id).await?;
+
+    Ok(sqlx::query!(
+        "
+            SELECT from_workflow_state_id, to_workflow_state_id, transitioned_at
+            FROM todo_state_transition
+            WHERE todo_id = $1
+            ORDER BY transitioned_at, id
+        ",
+        todo_id
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|_err| {
+        ErrorResponse::from(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch transitions.",
+        )
+    })?
+    .into_iter()
+    .map(|record| PublicStateTransition {
+        from_workflow_state_id: record.from_workflow_state_id,
+        to_workflow_state_id: record.to_workflow_state_id,
+        transitioned_at: DateTime::<Utc>::from_naive_utc_and_offset(record.transitioned_at, Utc),
+    })
+    .collect::<Vec<_>>()
+    .into())
+}
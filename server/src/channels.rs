@@ -0,0 +1,225 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+use axum::async_trait;
+use lettre::{message::Mailbox, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use reqwest::{redirect, Url};
+use sqlx::{postgres::Postgres, Transaction};
+
+use crate::scheduler::DueReminder;
+
+/// A way of delivering reminders to users. The scheduler picks the channel
+/// whose name matches the channel of the reminder.
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    /// The name of the channel, as stored in `reminder.channel`.
+    fn name(&self) -> &'static str;
+
+    /// Delivers a reminder. Channels storing the reminder in the database do
+    /// so in `transaction`, which also marks the reminder as sent.
+    async fn deliver(
+        &self,
+        reminder: &DueReminder,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> anyhow::Result<()>;
+}
+
+/// Stores reminders as notifications shown inside the app.
+pub struct InAppChannel;
+
+#[async_trait]
+impl NotificationChannel for InAppChannel {
+    fn name(&self) -> &'static str {
+        "in_app"
+    }
+
+    async fn deliver(
+        &self,
+        reminder: &DueReminder,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> anyhow::Result<()> {
+        sqlx::query!(
+            "
+                INSERT INTO notification (account_id, todo_id, message)
+                VALUES ($1, $2, $3)
+            ",
+            reminder.account_id,
+            reminder.todo_id,
+            reminder.message()
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Sends reminders by email through an SMTP server, by default the one
+/// running on the local machine.
+pub struct EmailChannel {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailChannel {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let host = std::env::var("EVE_SMTP_HOST").unwrap_or_else(|_| "localhost".to_string());
+        let port = std::env::var("EVE_SMTP_PORT")
+            .unwrap_or_else(|_| "25".to_string())
+            .parse::<u16>()?;
+        let from = std::env::var("EVE_SMTP_FROM")
+            .unwrap_or_else(|_| "Eve <eve@localhost>".to_string())
+            .parse::<Mailbox>()?;
+
+        Ok(EmailChannel {
+            transport: AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+                .port(port)
+                .build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for EmailChannel {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn deliver(
+        &self,
+        reminder: &DueReminder,
+        _transaction: &mut Transaction<'_, Postgres>,
+    ) -> anyhow::Result<()> {
+        let to = reminder
+            .email
+            .as_ref()
+            .ok_or_else(|| anyhow!("The user has no email address."))?
+            .parse::<Mailbox>()?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(format!("Reminder: {}", reminder.title))
+            .body(reminder.message())?;
+
+        self.transport
+            .send(message)
+            .await
+            .context("Failed to send email.")?;
+
+        Ok(())
+    }
+}
+
+/// Parses a webhook URL and resolves its host. Only https URLs whose host
+/// resolves exclusively to public addresses are accepted, so that webhooks
+/// cannot be used to reach services on the internal network.
+pub async fn resolve_webhook_url(webhook_url: &str) -> anyhow::Result<(Url, SocketAddr)> {
+    let url = Url::parse(webhook_url).map_err(|_err| anyhow!("Invalid webhook URL."))?;
+    if url.scheme() != "https" {
+        bail!("The webhook URL must use https.");
+    }
+    // IPv6 hosts are written in brackets, which the resolver does not accept.
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("Invalid webhook URL."))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    let addresses = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_err| anyhow!("The host of the webhook URL could not be resolved."))?
+        .collect::<Vec<_>>();
+    if addresses.iter().any(|address| !is_public_ip(address.ip())) {
+        bail!("The webhook URL must not point to a private address.");
+    }
+    let address = *addresses
+        .first()
+        .ok_or_else(|| anyhow!("The host of the webhook URL could not be resolved."))?;
+
+    Ok((url, address))
+}
+
+/// Whether an address is reachable on the public internet, as opposed to
+/// loopback, private, link-local, and other special-purpose addresses.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Shared address space (RFC 6598).
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Posts reminders as JSON to the webhook URL of the user.
+pub struct WebhookChannel;
+
+#[async_trait]
+impl NotificationChannel for WebhookChannel {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn deliver(
+        &self,
+        reminder: &DueReminder,
+        _transaction: &mut Transaction<'_, Postgres>,
+    ) -> anyhow::Result<()> {
+        let webhook_url = reminder
+            .webhook_url
+            .as_ref()
+            .ok_or_else(|| anyhow!("The user has no webhook URL."))?;
+
+        // The URL is checked again on delivery, as the host may resolve to a
+        // different address than when the user set it. The request is pinned
+        // to the checked address and does not follow redirects.
+        let (url, address) = resolve_webhook_url(webhook_url).await?;
+        let mut client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(redirect::Policy::none());
+        if let Some(domain) = url.domain() {
+            client = client.resolve(domain, address);
+        }
+
+        client
+            .build()?
+            .post(url)
+            .json(&serde_json::json!({
+                "reminder_id": reminder.reminder_id,
+                "todo_id": reminder.todo_id,
+                "title": reminder.title,
+                "deadline": reminder.local_deadline,
+                "time_zone": reminder.time_zone,
+                "message": reminder.message(),
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use axum::{
+    async_trait,
+    extract::{FromRequest, RequestParts},
+    http::{header, StatusCode},
+};
+
+/// Formats a row version as a strong entity tag.
+pub fn format_etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Parses a strong entity tag. Weak tags are rejected, as `If-Match` uses the
+/// strong comparison.
+fn parse_etag(etag: &str) -> Option<i32> {
+    etag.trim()
+        .strip_prefix('"')?
+        .strip_suffix('"')?
+        .parse()
+        .ok()
+}
+
+/// The version expected by the client, taken from the `If-Match` header.
+///
+/// `None` means the client did not send the header (or sent `*`), and the
+/// update should be applied unconditionally.
+pub struct IfMatch(pub Option<i32>);
+
+#[async_trait]
+impl<B> FromRequest<B> for IfMatch
+where
+    B: Send,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let if_match = match req.headers().get(header::IF_MATCH) {
+            Some(if_match) => if_match
+                .to_str()
+                .map_err(|_err| (StatusCode::BAD_REQUEST, "Invalid If-Match header."))?,
+            None => return Ok(IfMatch(None)),
+        };
+
+        if if_match.trim() == "*" {
+            return Ok(IfMatch(None));
+        }
+
+        parse_etag(if_match)
+            .map(|version| IfMatch(Some(version)))
+            .ok_or((StatusCode::BAD_REQUEST, "Invalid If-Match header."))
+    }
+}
@@ -1,5 +1,8 @@
 mod api;
 mod auth;
+mod channels;
+mod etag;
+mod scheduler;
 mod session;
 
 use axum::{extract::Extension, Router};
@@ -8,6 +11,8 @@ use std::net::SocketAddr;
 use tower_cookies::CookieManagerLayer;
 
 use api::get_api_router;
+use channels::{EmailChannel, InAppChannel, NotificationChannel, WebhookChannel};
+use scheduler::Scheduler;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -18,6 +23,13 @@ async fn main() -> anyhow::Result<()> {
         .connect(&std::env::var("DATABASE_URL")?)
         .await?;
 
+    let channels: Vec<Box<dyn NotificationChannel>> = vec![
+        Box::new(InAppChannel),
+        Box::new(EmailChannel::from_env()?),
+        Box::new(WebhookChannel),
+    ];
+    tokio::spawn(Scheduler::new(pg_pool.clone(), channels)?.run());
+
     let app = Router::new()
         .nest("/api", get_api_router())
         .layer(Extension(pg_pool))
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use sqlx::{Acquire, PgPool};
+
+use crate::channels::NotificationChannel;
+
+/// Deliveries of a reminder before it is given up on.
+const MAX_ATTEMPTS: i32 = 5;
+
+/// Reminders delivered per poll.
+const BATCH_SIZE: usize = 100;
+
+/// A reminder that is due, together with what is needed to deliver it.
+pub struct DueReminder {
+    pub reminder_id: i64,
+    pub todo_id: i64,
+    pub account_id: i32,
+    pub channel: String,
+    pub title: String,
+    /// The deadline of the todo in the time zone of the user.
+    pub local_deadline: Option<String>,
+    pub time_zone: String,
+    pub email: Option<String>,
+    pub webhook_url: Option<String>,
+}
+
+impl DueReminder {
+    pub fn message(&self) -> String {
+        match &self.local_deadline {
+            Some(local_deadline) => format!(
+                "Reminder: {} is due {} ({}).",
+                self.title, local_deadline, self.time_zone
+            ),
+            None => format!("Reminder: {}", self.title),
+        }
+    }
+}
+
+/// Fires due reminders through their channels.
+///
+/// Reminders are stored in the database and marked as sent only once they
+/// are delivered, so reminders that became due while the server was down are
+/// fired on the first poll after a restart. Failed deliveries are retried
+/// with a growing delay.
+pub struct Scheduler {
+    pg_pool: PgPool,
+    channels: Vec<Box<dyn NotificationChannel>>,
+    poll_interval: Duration,
+}
+
+impl Scheduler {
+    pub fn new(
+        pg_pool: PgPool,
+        channels: Vec<Box<dyn NotificationChannel>>,
+    ) -> anyhow::Result<Self> {
+        let poll_interval = std::env::var("EVE_REMINDER_POLL_INTERVAL_SECONDS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<u64>()?;
+
+        Ok(Scheduler {
+            pg_pool,
+            channels,
+            poll_interval: Duration::from_secs(poll_interval),
+        })
+    }
+
+    pub async fn run(self) {
+        loop {
+            if let Err(err) = self.fire_due_reminders().await {
+                tracing::error!("Failed to fire reminders: {:#}", err);
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    async fn fire_due_reminders(&self) -> anyhow::Result<()> {
+        for _ in 0..BATCH_SIZE {
+            if !self.fire_next_due_reminder().await? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delivers the earliest due reminder, if any, and records the outcome.
+    ///
+    /// Each reminder is claimed and recorded in its own transaction, so that
+    /// a failure while recording one reminder does not undo the records of
+    /// reminders delivered before it.
+    async fn fire_next_due_reminder(&self) -> anyhow::Result<bool> {
+        let mut transaction = self.pg_pool.begin().await?;
+
+        // Lock the due reminder, so that other server instances skip it
+        // instead of delivering it twice.
+        let reminder = sqlx::query_as!(
+            DueReminder,
+            "
+                SELECT
+                    reminder.id AS reminder_id,
+                    reminder.todo_id,
+                    todo.account_id,
+                    reminder.channel,
+                    todo.title,
+                    CASE
+                        WHEN todo.deadline IS NOT NULL THEN to_char(
+                            todo.deadline AT TIME ZONE 'UTC' AT TIME ZONE account.time_zone,
+                            'YYYY-MM-DD HH24:MI'
+                        )
+                        ELSE to_char(todo.deadline_date, 'YYYY-MM-DD')
+                    END AS local_deadline,
+                    account.time_zone,
+                    account.email,
+                    account.webhook_url
+                FROM reminder
+                JOIN todo ON todo.id = reminder.todo_id
+                JOIN account ON account.id = todo.account_id
+                WHERE reminder.sent_at IS NULL
+                    AND reminder.attempts < $1
+                    AND (reminder.retry_at IS NULL
+                        OR reminder.retry_at <= CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+                    AND todo.completed_at IS NULL
+                    AND NOT EXISTS (
+                        SELECT 1 FROM project
+                        WHERE project.id = todo.project_id
+                            AND (project.archived_at IS NOT NULL OR project.is_template)
+                    )
+                    AND reminder_fire_at(reminder.id) <= CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+                ORDER BY reminder_fire_at(reminder.id), reminder.id
+                LIMIT 1
+                FOR UPDATE OF reminder SKIP LOCKED
+            ",
+            MAX_ATTEMPTS
+        )
+        .fetch_optional(&mut transaction)
+        .await?;
+
+        let reminder = match reminder {
+            Some(reminder) => reminder,
+            None => return Ok(false),
+        };
+
+        // A failed delivery only rolls back what the channel stored, keeping
+        // the transaction usable for recording the failure.
+        let mut savepoint = transaction.begin().await?;
+        let result = match self
+            .channels
+            .iter()
+            .find(|channel| channel.name() == reminder.channel)
+        {
+            Some(channel) => channel.deliver(&reminder, &mut savepoint).await,
+            None => Err(anyhow!("Unknown channel {}.", reminder.channel)),
+        };
+        match result {
+            Ok(()) => {
+                savepoint.commit().await?;
+
+                sqlx::query!(
+                    "
+                        UPDATE reminder
+                        SET sent_at = CURRENT_TIMESTAMP AT TIME ZONE 'UTC',
+                            attempts = attempts + 1,
+                            retry_at = NULL,
+                            last_error = NULL
+                        WHERE id = $1
+                    ",
+                    reminder.reminder_id
+                )
+                .execute(&mut transaction)
+                .await?;
+            }
+            Err(err) => {
+                savepoint.rollback().await?;
+
+                tracing::warn!(
+                    "Failed to deliver reminder {}: {:#}",
+                    reminder.reminder_id,
+                    err
+                );
+
+                sqlx::query!(
+                    "
+                        UPDATE reminder
+                        SET attempts = attempts + 1,
+                            retry_at = CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+                                + (attempts + 1) * (attempts + 1) * INTERVAL '1 minute',
+                            last_error = $2
+                        WHERE id = $1
+                    ",
+                    reminder.reminder_id,
+                    format!("{:#}", err)
+                )
+                .execute(&mut transaction)
+                .await?;
+            }
+        }
+
+        transaction.commit().await?;
+
+        Ok(true)
+    }
+}